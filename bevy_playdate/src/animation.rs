@@ -0,0 +1,222 @@
+use crate::asset::{BitmapRef, BitmapTableAsset};
+use crate::sprite::{Sprite, SpriteSystemSet};
+use bevy_app::{App, Plugin, PostUpdate};
+use bevy_ecs::component::Component;
+use bevy_ecs::entity::Entity;
+use bevy_ecs::event::{Event, EventWriter};
+use bevy_ecs::prelude::{IntoScheduleConfigs, Query, Res};
+use bevy_platform::sync::Arc;
+use bevy_time::Time;
+use playdate::graphics::BitmapFlip;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AnimationFinished>().add_systems(
+            PostUpdate,
+            animate_sprites.before(SpriteSystemSet),
+        );
+    }
+}
+
+/// How an [`AnimatedSprite`] continues after its last frame.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum AnimationMode {
+    /// Start again from the first frame.
+    #[default]
+    Loop,
+    /// Play backwards to the first frame, then forwards again.
+    PingPong,
+    /// Stay on the last frame and send [`AnimationFinished`].
+    Once,
+}
+
+/// Sent once when an [`AnimatedSprite`] with [`AnimationMode::Once`] reaches its last frame.
+#[derive(Event, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct AnimationFinished {
+    pub entity: Entity,
+}
+
+/// A frame of an [`AnimatedSprite`] played with [`AnimatedSprite::with_frames`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct AnimationFrame {
+    /// Index of the bitmap in the table.
+    pub index: usize,
+    /// Seconds the frame is shown for.
+    pub duration: f32,
+}
+
+/// Plays the bitmaps of a [`BitmapTableAsset`] on the [`Sprite`] of the entity, one after another.
+///
+/// Frames advance with the [`Time`] resource, so playback keeps the same speed when frames are
+/// dropped.
+#[derive(Component, Clone)]
+#[require(Sprite)]
+pub struct AnimatedSprite {
+    table: Arc<BitmapTableAsset>,
+    /// Rate every bitmap of the table is played at, unless there are `frames`.
+    pub fps: f32,
+    frames: Option<Arc<[AnimationFrame]>>,
+    pub mode: AnimationMode,
+    /// Play the frames from last to first.
+    pub reversed: bool,
+    pub flip: BitmapFlip,
+    pub playing: bool,
+    /// Seconds since the animation started.
+    elapsed: f32,
+    finished: bool,
+    current: Option<usize>,
+}
+
+impl AnimatedSprite {
+    pub fn new(table: Arc<BitmapTableAsset>, fps: f32, mode: AnimationMode) -> Self {
+        Self {
+            table,
+            fps,
+            frames: None,
+            mode,
+            reversed: false,
+            flip: BitmapFlip::Unflipped,
+            playing: true,
+            elapsed: 0.0,
+            finished: false,
+            current: None,
+        }
+    }
+
+    /// Plays `frames` in order, each for its own duration, instead of every bitmap of the table
+    /// at `fps`.
+    pub fn with_frames(mut self, frames: Arc<[AnimationFrame]>) -> Self {
+        self.frames = Some(frames);
+        self
+    }
+
+    pub fn reversed(mut self) -> Self {
+        self.reversed = true;
+        self
+    }
+
+    pub fn table(&self) -> &Arc<BitmapTableAsset> {
+        &self.table
+    }
+
+    pub fn frame_count(&self) -> usize {
+        match &self.frames {
+            Some(frames) => frames.len(),
+            None => self.table.len(),
+        }
+    }
+
+    /// Plays the animation again from the start.
+    pub fn restart(&mut self) {
+        self.elapsed = 0.0;
+        self.finished = false;
+        self.playing = true;
+    }
+
+    /// True once an animation with [`AnimationMode::Once`] has shown its last frame.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Index in the table of the frame shown after `elapsed` seconds, and whether the animation
+    /// is past its end.
+    fn frame_at(&self, elapsed: f32) -> (usize, bool) {
+        let Some(frames) = &self.frames else {
+            return self.step_at((elapsed * self.fps) as usize);
+        };
+
+        // steps are frames of the sequence, so find how many have fully played
+        let total: f32 = frames.iter().map(|frame| frame.duration).sum();
+        let len = frames.len();
+        let step = if total <= 0.0 {
+            0
+        } else {
+            // ping-pong plays every frame but the first and last twice per period
+            let period = match self.mode {
+                AnimationMode::PingPong if len > 1 => {
+                    2.0 * total - frames[0].duration - frames[len - 1].duration
+                }
+                _ => total,
+            };
+            let periods = (elapsed / period) as usize;
+            let mut time = elapsed - periods as f32 * period;
+            let steps_per_period = match self.mode {
+                AnimationMode::PingPong if len > 1 => 2 * len - 2,
+                _ => len,
+            };
+
+            let mut step = 0;
+            while step < steps_per_period {
+                let i = if step < len { step } else { steps_per_period - step };
+                let duration = frames[if self.reversed { len - 1 - i } else { i }].duration;
+                if time < duration {
+                    break;
+                }
+                time -= duration;
+                step += 1;
+            }
+            periods * steps_per_period + step
+        };
+
+        let (i, finished) = self.step_at(step);
+        (frames[i].index, finished)
+    }
+
+    /// Index in the sequence of the frame shown after `step` frames, and whether the animation
+    /// is past its end.
+    fn step_at(&self, step: usize) -> (usize, bool) {
+        let len = self.frame_count();
+
+        let (frame, finished) = match self.mode {
+            AnimationMode::Loop => (step % len, false),
+            AnimationMode::PingPong if len > 1 => {
+                let period = 2 * len - 2;
+                let i = step % period;
+                (if i < len { i } else { period - i }, false)
+            }
+            AnimationMode::PingPong => (0, false),
+            AnimationMode::Once => (step.min(len - 1), step >= len),
+        };
+
+        if self.reversed {
+            (len - 1 - frame, finished)
+        } else {
+            (frame, finished)
+        }
+    }
+}
+
+pub fn animate_sprites(
+    time: Res<Time>,
+    mut q_sprites: Query<(Entity, &mut AnimatedSprite, &mut Sprite)>,
+    mut finished: EventWriter<AnimationFinished>,
+) {
+    for (entity, mut animation, mut sprite) in q_sprites.iter_mut() {
+        if animation.frame_count() == 0 {
+            continue;
+        }
+
+        if animation.playing && !animation.finished {
+            animation.elapsed += time.delta_secs();
+        }
+
+        let (frame, is_finished) = animation.frame_at(animation.elapsed);
+        if is_finished && !animation.finished {
+            animation.finished = true;
+            animation.playing = false;
+            finished.write(AnimationFinished { entity });
+        }
+
+        if animation.current == Some(frame) {
+            continue;
+        }
+
+        sprite.set_bitmap_ref(
+            BitmapRef::from_table(animation.table.clone(), frame),
+            animation.flip,
+        );
+        animation.current = Some(frame);
+    }
+}
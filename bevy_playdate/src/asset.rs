@@ -14,6 +14,7 @@ use playdate::graphics::api;
 use playdate::graphics::bitmap::Bitmap;
 use playdate::graphics::bitmap::table::BitmapTable;
 use playdate::graphics::error::ApiError;
+use playdate::graphics::text::{load_font, Font};
 use playdate::println;
 
 pub struct AssetPlugin;
@@ -111,6 +112,21 @@ impl AssetAsync for BitmapAsset {
     }
 }
 
+#[derive(Deref)]
+pub struct FontAsset(pub Font);
+
+// SAFETY: playdate is single threaded
+unsafe impl Send for FontAsset {}
+unsafe impl Sync for FontAsset {}
+
+impl AssetAsync for FontAsset {
+    type Error = ApiError;
+
+    async fn load(_load_cx: &mut AsyncLoadCtx, path: &str) -> Result<Self, Self::Error> {
+        Ok(FontAsset(load_font(path)?))
+    }
+}
+
 pub struct BitmapTableAsset {
     // only need this to keep ownership of images (freed on drop)
     _table: BitmapTable,
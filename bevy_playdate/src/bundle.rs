@@ -0,0 +1,136 @@
+use crate::file::{FileHandle, exists};
+use crate::jobs::{AsyncLoadCtx, GenJobExtensions};
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use bevy_platform::sync::{Arc, LazyLock, Mutex, RwLock, Weak};
+use diagnostic::dbg;
+use no_std_io2::io;
+use no_std_io2::io::{Read, Seek, SeekFrom};
+use pd_asset::archive::{AlignVec, OwnedArchived};
+use pd_asset::bundle::{ArchivedBundleIndex, BundleEntry, BundleHeader, Compression};
+
+/// Bundles currently mounted. A bundle stays mounted for as long as an [`Arc`] returned by
+/// [`mount`] is alive.
+static MOUNTED: LazyLock<RwLock<Vec<Weak<Bundle>>>> = LazyLock::new(|| RwLock::new(Vec::new()));
+
+/// How many bytes of a member are read before yielding to the next job.
+const READ_CHUNK: usize = 8 * 1024;
+
+/// A single file holding several assets, written by the editor.
+///
+/// The file is opened once and kept open, members are read with a seek and sequential reads.
+pub struct Bundle {
+    path: String,
+    file: Mutex<FileHandle>,
+    header: BundleHeader,
+    index: OwnedArchived<ArchivedBundleIndex>,
+}
+
+impl Bundle {
+    /// Opens the bundle at `path` and reads its index.
+    pub fn open(path: &str) -> io::Result<Self> {
+        let mut file = FileHandle::read_only(path)?;
+
+        let mut header = [0; BundleHeader::SIZE];
+        file.read_exact(&mut header)?;
+        let header = BundleHeader::from_bytes(&header).map_err(|err| {
+            dbg!(path, err);
+            io::Error::new(io::ErrorKind::InvalidData, "Invalid bundle header")
+        })?;
+
+        let mut index = AlignVec::with_capacity(header.index_len as usize);
+        index.resize(header.index_len as usize, 0);
+        file.read_exact(&mut index)?;
+        let index = OwnedArchived::new(index)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid bundle index"))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            file: Mutex::new(file),
+            header,
+            index,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns where the member with the given path is in the bundle, if it is in it.
+    pub fn entry(&self, path: &str) -> Option<BundleEntry> {
+        self.index.access().entries.get(path).map(BundleEntry::from)
+    }
+
+    /// Reads (and decompresses) a member of the bundle, yielding between chunks.
+    pub async fn read(&self, load_cx: &mut AsyncLoadCtx, entry: BundleEntry) -> io::Result<Vec<u8>> {
+        let start = self.header.payload_start() + entry.offset as u64;
+        let len = entry.len as usize;
+        let mut bytes = vec![0; len];
+
+        let mut read = 0;
+        while read < len {
+            let end = (read + READ_CHUNK).min(len);
+            {
+                // don't hold the lock across the yield, other jobs may read from this bundle too
+                let mut file = self.file.lock().unwrap();
+                file.seek(SeekFrom::Start(start + read as u64))?;
+                file.read_exact(&mut bytes[read..end])?;
+            }
+            read = end;
+            load_cx.yield_next().await;
+        }
+
+        match entry.compression {
+            Compression::None => Ok(bytes),
+            Compression::Lz4 => lz4_flex::decompress(&bytes, entry.uncompressed_len as usize)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Corrupt bundle member")),
+        }
+    }
+}
+
+/// Opens the bundle at `path` (or reuses it if already mounted) so that
+/// [`load_file_bytes`](crate::jobs::load_file_bytes) reads its members from it.
+///
+/// The bundle is unmounted when the returned [`Arc`] and all clones of it are dropped.
+pub fn mount(path: &str) -> io::Result<Arc<Bundle>> {
+    let mut mounted = MOUNTED.write().unwrap();
+    mounted.retain(|bundle| bundle.strong_count() > 0);
+    if let Some(bundle) = mounted
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|bundle| bundle.path == path)
+    {
+        return Ok(bundle);
+    }
+
+    let bundle = Arc::new(Bundle::open(path)?);
+    mounted.push(Arc::downgrade(&bundle));
+
+    Ok(bundle)
+}
+
+/// Mounts the bundle at `path` if there is one. Errors other than the file not existing are
+/// logged, and the members are read from their own files instead.
+pub fn mount_if_exists(path: &str) -> Option<Arc<Bundle>> {
+    if !exists(path) {
+        return None;
+    }
+
+    match mount(path) {
+        Ok(bundle) => Some(bundle),
+        Err(err) => {
+            dbg!(path, err);
+            None
+        }
+    }
+}
+
+/// Finds the mounted bundle containing the file at `path`.
+pub fn find(path: &str) -> Option<(Arc<Bundle>, BundleEntry)> {
+    let mounted = MOUNTED.read().unwrap();
+    mounted.iter().filter_map(Weak::upgrade).find_map(|bundle| {
+        let entry = bundle.entry(path)?;
+        Some((bundle, entry))
+    })
+}
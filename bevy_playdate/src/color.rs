@@ -0,0 +1,120 @@
+use bevy_app::{App, Plugin};
+use bevy_reflect::Reflect;
+use bevy_reflect::prelude::ReflectDefault;
+use playdate::graphics::color::Color;
+use playdate::sys::ffi::LCDPattern;
+
+pub struct ColorPlugin;
+
+impl Plugin for ColorPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LcdColor>();
+    }
+}
+
+/// A color the Playdate screen can draw with.
+///
+/// Tiled `color` properties are converted with [`LcdColor::from_rgba`]. Since no RGBA color maps to
+/// [`Xor`](Self::Xor), use a string property with the variant name instead.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Reflect)]
+#[reflect(Default, Debug, PartialEq, Clone)]
+pub enum LcdColor {
+    #[default]
+    Black,
+    White,
+    Clear,
+    Xor,
+    /// An 8x8 pattern: the first 8 bytes are the rows of the bitmap (set bits are white),
+    /// the last 8 bytes are the rows of the mask (set bits are opaque).
+    Pattern(LCDPattern),
+}
+
+/// 8x8 ordered dither threshold map, with values `0..64`.
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Number of distinct levels an 8x8 pattern can represent, minus one.
+const LEVELS: u32 = 64;
+
+impl LcdColor {
+    /// Picks the closest Playdate color for an 8-bit RGBA color.
+    ///
+    /// Fully opaque black and white become [`Black`](Self::Black) and [`White`](Self::White),
+    /// fully transparent colors become [`Clear`](Self::Clear). Everything else becomes a
+    /// [`Pattern`](Self::Pattern) dithered from the luminance, with the mask dithered from the alpha.
+    pub fn from_rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        let luminance = (299 * red as u32 + 587 * green as u32 + 114 * blue as u32) / 1000;
+        let white = to_level(luminance);
+        let opaque = to_level(alpha as u32);
+
+        match (white, opaque) {
+            (_, 0) => Self::Clear,
+            (0, LEVELS) => Self::Black,
+            (LEVELS, LEVELS) => Self::White,
+            (white, opaque) => {
+                let mut pattern = [0; 16];
+                pattern[..8].copy_from_slice(&dither(white));
+                pattern[8..].copy_from_slice(&dither(opaque));
+                Self::Pattern(pattern)
+            }
+        }
+    }
+
+    /// The inverse of [`LcdColor::from_rgba`], up to dithering precision.
+    ///
+    /// [`Xor`](Self::Xor) has no RGBA equivalent and maps to opaque mid-grey.
+    pub fn to_rgba(&self) -> [u8; 4] {
+        match self {
+            Self::Black => [0, 0, 0, 255],
+            Self::White => [255, 255, 255, 255],
+            Self::Clear => [0, 0, 0, 0],
+            Self::Xor => [128, 128, 128, 255],
+            Self::Pattern(pattern) => {
+                let (bitmap, mask) = pattern.split_at(8);
+                let opaque: u32 = mask.iter().map(|row| row.count_ones()).sum();
+                let white: u32 = bitmap
+                    .iter()
+                    .zip(mask)
+                    .map(|(row, mask)| (row & mask).count_ones())
+                    .sum();
+                let luminance = (white * 255).checked_div(opaque).unwrap_or(0) as u8;
+                let alpha = (opaque * 255 / LEVELS) as u8;
+
+                [luminance, luminance, luminance, alpha]
+            }
+        }
+    }
+
+    pub fn as_color(&self) -> Color<'_> {
+        match self {
+            Self::Black => Color::BLACK,
+            Self::White => Color::WHITE,
+            Self::Clear => Color::CLEAR,
+            Self::Xor => Color::XOR,
+            Self::Pattern(pattern) => Color::Pattern(pattern),
+        }
+    }
+}
+
+/// Maps `0..=255` to `0..=LEVELS`, rounding to nearest.
+fn to_level(value: u32) -> u32 {
+    (value * LEVELS + 127) / 255
+}
+
+/// Rows of an 8x8 pattern with `level` bits set.
+fn dither(level: u32) -> [u8; 8] {
+    BAYER_8X8.map(|row| {
+        row.iter()
+            .enumerate()
+            .filter(|(_, threshold)| (**threshold as u32) < level)
+            .fold(0, |acc, (x, _)| acc | (0x80 >> x))
+    })
+}
@@ -7,7 +7,16 @@ use core::ffi::c_void;
 use no_std_io2::io;
 use no_std_io2::io::{Read, Seek, SeekFrom, Write};
 use playdate::sys as playdate_sys;
-use playdate::sys::ffi::{FileOptions, SDFile};
+use playdate::sys::ffi::{FileOptions, FileStat, SDFile};
+
+/// Returns true if there is a file at `path`, in the game pdx or the data folder.
+pub fn exists(path: &str) -> bool {
+    let Ok(c_path) = CString::new(path) else {
+        return false;
+    };
+    let mut stat: FileStat = unsafe { core::mem::zeroed() };
+    unsafe { playdate_sys::api!(file).stat.unwrap()(c_path.as_ptr(), &mut stat) == 0 }
+}
 
 pub struct FileHandle {
     handle: *mut SDFile,
@@ -514,7 +514,15 @@ impl GenJobExtensions for Co<JobRequest, JobResponse> {
     }
 }
 
+/// Reads the whole file at `path`, yielding between reads.
+///
+/// If the file is a member of a mounted [`Bundle`](crate::bundle::Bundle), it is read from the
+/// bundle instead of opening its own file.
 pub async fn load_file_bytes(load_cx: &mut AsyncLoadCtx, path: &str) -> Result<Vec<u8>, Error> {
+    if let Some((bundle, entry)) = crate::bundle::find(path) {
+        return bundle.read(load_cx, entry).await;
+    }
+
     let mut file = FileHandle::read_only(path)?;
     let mut bytes = Vec::with_capacity(128);
     let mut file_length = 0;
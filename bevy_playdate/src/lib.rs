@@ -2,7 +2,10 @@
 #![no_std]
 
 pub mod angle;
+pub mod animation;
 pub mod asset;
+pub mod bundle;
+pub mod color;
 pub mod debug;
 pub mod event;
 pub mod file;
@@ -26,7 +29,9 @@ impl Plugin for DefaultPlugins {
     fn build(&self, app: &mut App) {
         app.add_plugins((
             input::InputPlugin,
+            color::ColorPlugin,
             sprite::SpritePlugin,
+            animation::AnimationPlugin,
             time::PDTimePlugin,
             debug::DebugPlugin,
             view::ViewPlugin,
@@ -100,6 +100,12 @@ impl Sprite {
         self.spr.set_image(&*bitmap, BitmapFlip::Unflipped);
     }
 
+    /// Replaces the image of the sprite, keeping `bitmap` alive for as long as it's used.
+    pub fn set_bitmap_ref(&mut self, bitmap: BitmapRef, flip: BitmapFlip) {
+        self.spr.set_image(&bitmap.as_ref().0, flip);
+        self.bitmap = bitmap;
+    }
+
     // /// System to draw all sprites to the screen. Calls [`playdate::sprite::draw_sprites`].
     // ///
     // /// If your draw calls are not showing up, order that system after this one.
@@ -1,5 +1,6 @@
 use crate::sprite::Sprite;
 use crate::transform::{GlobalTransform, Transform};
+use alloc::vec::Vec;
 use bevy_app::{App, Plugin, PostUpdate};
 use bevy_ecs::change_detection::*;
 use bevy_ecs::prelude::*;
@@ -9,6 +10,9 @@ use bevy_reflect::prelude::ReflectDefault;
 use playdate::graphics::Graphics;
 use playdate::sprite::draw_sprites;
 
+/// Size of the Playdate screen in pixels.
+pub const SCREEN_SIZE: IVec2 = IVec2::new(400, 240);
+
 pub struct ViewPlugin;
 
 impl Plugin for ViewPlugin {
@@ -16,14 +20,17 @@ impl Plugin for ViewPlugin {
         app.add_systems(
             PostUpdate,
             (
-                (camera_offset, sync_sprite_transform, reset_removed_camera),
+                (camera_offset, reset_removed_camera),
+                scroll_parallax,
+                sync_sprite_transform,
                 update_offset
             ).chain()
                 .after(crate::transform::TransformSystem::TransformPropagate)
                 .before(draw_sprites),
         )
             .init_resource::<DrawOffset>()
-            .register_type::<Camera>();
+            .register_type::<Camera>()
+            .register_type::<Parallax>();
     }
 }
 
@@ -63,7 +70,7 @@ pub fn camera_offset(
     let (camera, transform) = camera.into_inner();
 
     let mut pos = transform.0;
-    pos += Vec2::new(-200.0, -120.0);
+    pos -= (SCREEN_SIZE / 2).as_vec2();
     pos += camera.offset;
     
     offset.0 = IVec2::new(-pos.x as i32, -pos.y as i32);
@@ -79,15 +86,84 @@ pub fn reset_removed_camera(
     }
 }
 
+/// Makes the sprites of an entity and its descendants scroll at `factor` times the speed of the
+/// [`Camera`], the same as a layer with a parallax factor in Tiled.
+///
+/// At a factor of 1 sprites are drawn at their [`GlobalTransform`]. Sprites are offset from it by
+/// how far the center of the screen is from the world origin, times `1 - factor`. If nested, the
+/// closest [`Parallax`] is used.
+#[derive(Component, Copy, Clone, PartialEq, Debug, Reflect)]
+#[reflect(Component, Default, Clone)]
+pub struct Parallax {
+    pub factor: Vec2,
+    shift: Vec2,
+}
+
+impl Parallax {
+    pub fn new(factor: Vec2) -> Self {
+        Self {
+            factor,
+            shift: Vec2::ZERO,
+        }
+    }
+
+    /// How far the sprites are currently drawn from their [`GlobalTransform`].
+    pub fn shift(&self) -> Vec2 {
+        self.shift
+    }
+}
+
+impl Default for Parallax {
+    fn default() -> Self {
+        Self::new(Vec2::ONE)
+    }
+}
+
+/// Updates the shift of every [`Parallax`] from the [`DrawOffset`], moving the sprites under
+/// the ones that changed.
+pub fn scroll_parallax(
+    mut q_parallax: Query<(Entity, &mut Parallax)>,
+    q_nested: Query<(), With<Parallax>>,
+    q_children: Query<&Children>,
+    q_sprite: Query<(&GlobalTransform, &Sprite)>,
+    offset: Res<DrawOffset>,
+) {
+    let center = (offset.top_left() + SCREEN_SIZE / 2).as_vec2();
+
+    for (entity, mut parallax) in q_parallax.iter_mut() {
+        let shift = (center * (Vec2::ONE - parallax.factor)).round();
+        if parallax.shift == shift {
+            continue;
+        }
+        parallax.shift = shift;
+
+        let mut to_visit = Vec::from([entity]);
+        while let Some(e) = to_visit.pop() {
+            if let Ok((transform, sprite)) = q_sprite.get(e) {
+                sprite.move_to(transform.x + shift.x, transform.y + shift.y);
+            }
+            // nested parallax entities move their own sprites
+            let children = q_children.get(e).into_iter().flatten();
+            to_visit.extend(children.filter(|child| !q_nested.contains(**child)));
+        }
+    }
+}
+
 #[allow(clippy::type_complexity)]
 pub fn sync_sprite_transform(
     mut q_sprite: Query<
-        (&GlobalTransform, &mut Sprite),
+        (Entity, &GlobalTransform, &mut Sprite),
         Or<(Changed<GlobalTransform>, Added<Sprite>)>,
     >,
+    q_parents: Query<&ChildOf>,
+    q_parallax: Query<&Parallax>,
 ) {
-    for (transform, spr) in q_sprite.iter_mut() {
-        spr.move_to(transform.x, transform.y);
+    for (entity, transform, spr) in q_sprite.iter_mut() {
+        let shift = core::iter::once(entity)
+            .chain(q_parents.iter_ancestors(entity))
+            .find_map(|e| q_parallax.get(e).ok())
+            .map_or(Vec2::ZERO, Parallax::shift);
+        spr.move_to(transform.x + shift.x, transform.y + shift.y);
     }
 }
 
@@ -0,0 +1,170 @@
+//! Reads Aseprite files, so artists never export sprite sheets by hand.
+//!
+//! Before any asset is converted, [`sync_sheets`] saves every `.aseprite` file in the assets
+//! folder as a sprite sheet PNG next to it, so Tiled and baked layers see the same image:
+//!
+//! - a file with a single frame is a sheet drawn by hand, like a tileset, and becomes
+//!   `<name>-table-W-H.png` with the cells of its grid (or `<name>.png` if its name already ends
+//!   in `-table-W-H`)
+//! - a file with more frames becomes `<name>-table-W-H.png` with a cell per frame.
+//!
+//! Only visible layers are drawn. Each tag, or the whole file if it has more than one frame and
+//! no tags, is also exported as a [`Gif`](pd_asset::gif::Gif) with the durations of its frames.
+
+use crate::path::to_slash;
+use crate::{files_in, paths};
+use anyhow::anyhow;
+use asefile::{AnimationDirection, AsepriteFile};
+use image::{GenericImage, RgbaImage};
+use pd_asset::gif::GifFrame;
+use regex::Regex;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Most cells in a row of a sheet made from frames.
+const MAX_COLUMNS: u32 = 16;
+
+static TABLE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^(?<name>.*)-table-\d+(?:-\d+)?$"#).unwrap());
+
+pub struct Aseprite {
+    file: AsepriteFile,
+    /// Cell size of the grid of the file.
+    grid: (u32, u32),
+}
+
+impl Aseprite {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file =
+            AsepriteFile::read_file(path).map_err(|err| anyhow!("{}: {err:?}", path.display()))?;
+
+        // asefile doesn't read the grid, which is at a fixed place in the header
+        let header = fs::read(path)?;
+        let word = |offset: usize| {
+            header
+                .get(offset..offset + 2)
+                .map_or(0, |bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as u32)
+        };
+        let grid = match (word(40), word(42)) {
+            (0, _) | (_, 0) => (file.width() as u32, file.height() as u32),
+            grid => grid,
+        };
+
+        Ok(Self { file, grid })
+    }
+
+    fn frames(&self) -> u32 {
+        self.file.num_frames()
+    }
+
+    /// Name of the image table on the Playdate, and file name of the sheet, for the file at
+    /// `path`.
+    pub fn names(&self, path: &Path) -> (String, String) {
+        let stem = path.file_stem().unwrap().to_string_lossy();
+        let table = TABLE_REGEX.captures(&stem).filter(|_| self.frames() == 1);
+        if let Some(captures) = table {
+            return (captures["name"].to_string(), format!("{stem}.png"));
+        }
+
+        let (width, height) = if self.frames() == 1 {
+            self.grid
+        } else {
+            (self.file.width() as u32, self.file.height() as u32)
+        };
+        (stem.to_string(), format!("{stem}-table-{width}-{height}.png"))
+    }
+
+    fn frame_image(&self, frame: u32) -> RgbaImage {
+        let image = self.file.frame(frame).image();
+        RgbaImage::from_raw(image.width(), image.height(), image.into_raw()).unwrap()
+    }
+
+    /// The sprite sheet, with the visible layers of each frame.
+    pub fn sheet(&self) -> RgbaImage {
+        if self.frames() == 1 {
+            return self.frame_image(0);
+        }
+
+        let (width, height) = (self.file.width() as u32, self.file.height() as u32);
+        let columns = self.frames().min(MAX_COLUMNS);
+        let rows = self.frames().div_ceil(columns);
+        let mut sheet = RgbaImage::new(width * columns, height * rows);
+        for frame in 0..self.frames() {
+            let (x, y) = (frame % columns * width, frame / columns * height);
+            sheet.copy_from(&self.frame_image(frame), x, y).unwrap();
+        }
+        sheet
+    }
+
+    /// The animations to export as `<name>.gifb` next to the file at `path`, with their frames.
+    pub fn animations(&self, path: &Path) -> Vec<(String, Vec<GifFrame>)> {
+        let stem = path.file_stem().unwrap().to_string_lossy();
+        let frame = |index: u32| GifFrame {
+            index,
+            duration: self.file.frame(index).duration(),
+        };
+
+        if self.file.num_tags() == 0 {
+            if self.frames() == 1 {
+                return Vec::new();
+            }
+            return vec![(stem.to_string(), (0..self.frames()).map(frame).collect())];
+        }
+
+        (0..self.file.num_tags())
+            .map(|i| {
+                let tag = self.file.tag(i);
+                let mut frames: Vec<GifFrame> =
+                    (tag.from_frame()..=tag.to_frame()).map(frame).collect();
+                let direction = tag.animation_direction();
+                if direction == AnimationDirection::Reverse {
+                    frames.reverse();
+                } else if direction == AnimationDirection::PingPong && frames.len() > 2 {
+                    // back again without repeating the ends, so looping plays it back and forth
+                    let back: Vec<GifFrame> =
+                        frames[1..frames.len() - 1].iter().rev().copied().collect();
+                    frames.extend(back);
+                }
+                (format!("{stem}-{}", tag.name()), frames)
+            })
+            .collect()
+    }
+}
+
+/// The Aseprite file the sheet at `png` (relative to the assets folder) is saved from, if there is
+/// one, relative to the assets folder.
+pub fn source_of(png: &Path) -> Option<PathBuf> {
+    let stem = png.file_stem()?.to_string_lossy();
+    let mut candidates = vec![png.with_extension("aseprite")];
+    if let Some(captures) = TABLE_REGEX.captures(&stem) {
+        candidates.push(png.with_file_name(format!("{}.aseprite", &captures["name"])));
+    }
+    candidates
+        .into_iter()
+        .find(|candidate| paths().assets.join(candidate).is_file())
+}
+
+/// Saves the sheet of every Aseprite file in the assets folder that changed since its sheet was
+/// last saved.
+pub fn sync_sheets() -> anyhow::Result<()> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+
+    for file in files_in(&paths().assets)? {
+        if file.extension() != Some(OsStr::new("aseprite")) {
+            continue;
+        }
+
+        let aseprite = Aseprite::load(&file)?;
+        let sheet = file.with_file_name(aseprite.names(&file).1);
+        if modified(&sheet) >= modified(&file) {
+            continue;
+        }
+
+        let relative = sheet.strip_prefix(&paths().assets).unwrap_or(&sheet);
+        println!("  sheet       {}", to_slash(relative));
+        aseprite.sheet().save(&sheet)?;
+    }
+    Ok(())
+}
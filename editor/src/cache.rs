@@ -0,0 +1,131 @@
+//! Build cache, so `editor build` only converts the assets that changed since the last build.
+//!
+//! Every converted asset is stored with a key hashing its source file and the keys of the assets
+//! it references, so changing a tileset image also changes the key of the tileset and of every
+//! map using it. An asset is skipped if its key matches and its exports are all still there. The
+//! whole cache is thrown away when the [converter version](converter_version) changes.
+
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Name of the cache file, in the export folder so `editor clean` deletes it too.
+pub const CACHE_FILE: &str = ".build-cache.json";
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct BuildCache {
+    pub version: u64,
+    /// Keyed by asset, relative to the assets folder.
+    pub entries: HashMap<PathBuf, CacheEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CacheEntry {
+    pub key: u64,
+    /// Assets it references, relative to the assets folder.
+    pub dependencies: Vec<PathBuf>,
+    /// Files it was converted from that aren't assets of their own, like object templates,
+    /// relative to the assets folder.
+    #[serde(default)]
+    pub sources: Vec<PathBuf>,
+    /// Files it exported, relative to the export folder.
+    pub outputs: Vec<PathBuf>,
+}
+
+impl BuildCache {
+    /// Where the cache of the exports in `export` is.
+    pub fn path(export: &Path) -> PathBuf {
+        export.join(CACHE_FILE)
+    }
+
+    /// Loads the cache of the last build into `export`, or an empty cache if there was none, it
+    /// can't be read or it was built by a different converter.
+    pub fn load(export: &Path, version: u64) -> Self {
+        let cache = std::fs::read(Self::path(export))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<BuildCache>(&bytes).ok())
+            .filter(|cache| cache.version == version);
+        cache.unwrap_or(BuildCache {
+            version,
+            entries: HashMap::new(),
+        })
+    }
+
+    pub fn save(&self, export: &Path) -> anyhow::Result<()> {
+        let path = Self::path(export);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// The entry of `asset` if it is up to date and its exports are all in `export`, computing
+    /// keys with the dependencies and sources recorded in the cache.
+    pub fn fresh(&self, asset: &Path, keys: &mut Keys, export: &Path) -> Option<&CacheEntry> {
+        let entry = self.entries.get(asset)?;
+        let dependencies = |asset: &Path| {
+            self.entries
+                .get(asset)
+                .map(|entry| [entry.dependencies.as_slice(), &entry.sources].concat())
+                .unwrap_or_default()
+        };
+        let exported = entry.outputs.iter().all(|output| export.join(output).is_file());
+
+        (exported && keys.key(asset, &dependencies) == entry.key).then_some(entry)
+    }
+}
+
+/// Computes the keys of assets, remembering them so shared dependencies are only read once.
+#[derive(Default)]
+pub struct Keys {
+    keys: HashMap<PathBuf, u64>,
+}
+
+impl Keys {
+    /// Hashes the source file of `asset` (relative to the assets folder) and the keys of
+    /// everything `dependencies` says it was converted from. A missing file hashes as a marker,
+    /// so deleting a file changes the key.
+    pub fn key(&mut self, asset: &Path, dependencies: &impl Fn(&Path) -> Vec<PathBuf>) -> u64 {
+        if let Some(key) = self.keys.get(asset) {
+            return *key;
+        }
+        // reference cycles hash the asset already being hashed as 0
+        self.keys.insert(asset.to_path_buf(), 0);
+
+        let mut hasher = DefaultHasher::new();
+        match std::fs::read(paths().assets.join(asset)) {
+            Ok(bytes) => bytes.hash(&mut hasher),
+            Err(_) => "missing".hash(&mut hasher),
+        }
+        for dependency in dependencies(asset) {
+            dependency.hash(&mut hasher);
+            self.key(&dependency, dependencies).hash(&mut hasher);
+        }
+
+        let key = hasher.finish();
+        self.keys.insert(asset.to_path_buf(), key);
+        key
+    }
+}
+
+/// Hashes everything besides the source files that changes what the converter outputs: the
+/// archive schema, the editor binary itself, the type registry properties are validated against
+/// (if properties are validated) and the conversion `settings` of the manifest.
+///
+/// [`DefaultHasher`] may change between Rust releases, which only costs a full rebuild.
+pub fn converter_version(type_export: Option<&Path>, settings: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    pd_asset::header::SCHEMA_VERSION.hash(&mut hasher);
+    std::env::current_exe()
+        .and_then(std::fs::read)
+        .ok()
+        .hash(&mut hasher);
+    type_export
+        .and_then(|path| std::fs::read(path).ok())
+        .hash(&mut hasher);
+    settings.hash(&mut hasher);
+    hasher.finish()
+}
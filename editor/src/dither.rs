@@ -0,0 +1,340 @@
+//! Quantizes images to the black, white and transparent of the Playdate screen, so exported
+//! images look like the previews artists check instead of depending on how the SDK compiler
+//! converts them.
+//!
+//! Images use the [`Dither`] of the `[dither]` table of the manifest:
+//!
+//! ```toml
+//! [dither]
+//! "*" = "threshold:100"       # every other image
+//! "background.png" = "atkinson"
+//! ```
+//!
+//! Baked tile layers can instead set a `dither` string property.
+
+use anyhow::{Context, bail};
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml_edit::{DocumentMut, Item};
+
+/// 8x8 ordered dither threshold map, with values `0..64`.
+pub const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Pixels with less alpha than this are transparent, the rest are opaque.
+const ALPHA_THRESHOLD: u8 = 128;
+
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const CLEAR: Rgba<u8> = Rgba([0; 4]);
+
+/// The colours the Playdate screen shows black and white as, for previews.
+const SCREEN_BLACK: Rgba<u8> = Rgba([50, 47, 41, 255]);
+const SCREEN_WHITE: Rgba<u8> = Rgba([215, 212, 204, 255]);
+
+/// How an image is reduced to black and white, written in the manifest or a layer property as
+/// `threshold`, `threshold:<0-255>`, `floyd-steinberg`, `atkinson` or `bayer`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Dither {
+    /// Pixels at least this light are white, the rest black.
+    Threshold(u8),
+    /// Error diffusion, spreading all of the error to the neighbouring pixels.
+    FloydSteinberg,
+    /// Error diffusion, spreading 3/4 of the error, which keeps more contrast.
+    Atkinson,
+    /// Ordered dithering with [`BAYER_8X8`].
+    Bayer,
+}
+
+impl Default for Dither {
+    fn default() -> Self {
+        Dither::Threshold(128)
+    }
+}
+
+impl FromStr for Dither {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.split_once(':') {
+            Some(("threshold", threshold)) => Dither::Threshold(
+                threshold
+                    .parse()
+                    .with_context(|| format!("threshold {threshold:?} must be 0 to 255"))?,
+            ),
+            None if s == "threshold" => Dither::default(),
+            None if s == "floyd-steinberg" => Dither::FloydSteinberg,
+            None if s == "atkinson" => Dither::Atkinson,
+            None if s == "bayer" => Dither::Bayer,
+            _ => bail!(
+                "unknown dither {s:?}, expected threshold, threshold:<0-255>, floyd-steinberg, \
+                 atkinson or bayer"
+            ),
+        })
+    }
+}
+
+impl Dither {
+    /// Makes every pixel of `image` black, white or transparent.
+    pub fn apply(self, image: &mut RgbaImage) {
+        let (width, height) = image.dimensions();
+        // with the error diffused into each pixel so far
+        let mut luma: Vec<f32> = image.pixels().map(luma).collect();
+        let kernel: &[(i32, i32, f32)] = match self {
+            Dither::FloydSteinberg => &[
+                (1, 0, 7.0 / 16.0),
+                (-1, 1, 3.0 / 16.0),
+                (0, 1, 5.0 / 16.0),
+                (1, 1, 1.0 / 16.0),
+            ],
+            Dither::Atkinson => &[
+                (1, 0, 1.0 / 8.0),
+                (2, 0, 1.0 / 8.0),
+                (-1, 1, 1.0 / 8.0),
+                (0, 1, 1.0 / 8.0),
+                (1, 1, 1.0 / 8.0),
+                (0, 2, 1.0 / 8.0),
+            ],
+            Dither::Threshold(_) | Dither::Bayer => &[],
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = image.get_pixel_mut(x, y);
+                if pixel.0[3] < ALPHA_THRESHOLD {
+                    *pixel = CLEAR;
+                    continue;
+                }
+
+                let value = luma[(x + y * width) as usize];
+                let white = match self {
+                    Dither::Threshold(threshold) => value >= threshold as f32,
+                    Dither::Bayer => {
+                        let threshold = BAYER_8X8[(y % 8) as usize][(x % 8) as usize];
+                        value > (threshold as f32 + 0.5) * 4.0
+                    }
+                    Dither::FloydSteinberg | Dither::Atkinson => value >= 128.0,
+                };
+                *pixel = if white { WHITE } else { BLACK };
+
+                let error = value - if white { 255.0 } else { 0.0 };
+                for &(dx, dy, weight) in kernel {
+                    let (x, y) = (x as i32 + dx, y as i32 + dy);
+                    if x < 0 || x >= width as i32 || y >= height as i32 {
+                        continue;
+                    }
+                    luma[(x + y * width as i32) as usize] += error * weight;
+                }
+            }
+        }
+    }
+}
+
+fn luma(pixel: &Rgba<u8>) -> f32 {
+    let [r, g, b, _] = pixel.0;
+    0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32
+}
+
+/// A quantized image in the colours of the Playdate screen.
+pub fn preview(image: &RgbaImage) -> RgbaImage {
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let pixel = image.get_pixel(x, y);
+        if pixel.0[3] < ALPHA_THRESHOLD {
+            CLEAR
+        } else if luma(pixel) >= 128.0 {
+            SCREEN_WHITE
+        } else {
+            SCREEN_BLACK
+        }
+    })
+}
+
+/// The `[dither]` table of the manifest.
+#[derive(Default, Debug)]
+pub struct DitherSettings {
+    /// For images not in `images`, from the `"*"` key.
+    pub default: Dither,
+    /// Relative to the assets folder.
+    pub images: HashMap<PathBuf, Dither>,
+}
+
+impl DitherSettings {
+    pub fn from_manifest(manifest: &DocumentMut) -> anyhow::Result<Self> {
+        let mut settings = DitherSettings::default();
+        let Some(table) = manifest.get("dither").and_then(Item::as_table) else {
+            return Ok(settings);
+        };
+
+        for (image, dither) in table.iter() {
+            let dither = dither
+                .as_str()
+                .with_context(|| format!("dither of {image:?} must be a string"))?
+                .parse()
+                .with_context(|| format!("dither of {image:?}"))?;
+            if image == "*" {
+                settings.default = dither;
+            } else {
+                settings
+                    .images
+                    .insert(crate::path::normalize(Path::new(image)), dither);
+            }
+        }
+        Ok(settings)
+    }
+
+    /// The dither of the image at `path`, relative to the assets folder.
+    pub fn for_image(&self, path: &Path) -> Dither {
+        self.images.get(path).copied().unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dither::{BLACK, CLEAR, Dither, DitherSettings, WHITE, luma};
+    use image::{Rgba, RgbaImage};
+    use std::path::Path;
+    use toml_edit::DocumentMut;
+
+    const ALL: [Dither; 4] = [
+        Dither::Threshold(128),
+        Dither::FloydSteinberg,
+        Dither::Atkinson,
+        Dither::Bayer,
+    ];
+
+    fn gray(value: u8) -> Rgba<u8> {
+        Rgba([value, value, value, 255])
+    }
+
+    fn dithered(dither: Dither, mut image: RgbaImage) -> RgbaImage {
+        dither.apply(&mut image);
+        image
+    }
+
+    fn white_fraction(image: &RgbaImage) -> f32 {
+        let white = image.pixels().filter(|pixel| **pixel == WHITE).count();
+        white as f32 / image.pixels().len() as f32
+    }
+
+    #[test]
+    pub fn parse() {
+        assert_eq!("threshold".parse::<Dither>().unwrap(), Dither::Threshold(128));
+        assert_eq!("threshold:0".parse::<Dither>().unwrap(), Dither::Threshold(0));
+        assert_eq!("threshold:255".parse::<Dither>().unwrap(), Dither::Threshold(255));
+        assert_eq!("floyd-steinberg".parse::<Dither>().unwrap(), Dither::FloydSteinberg);
+        assert_eq!("atkinson".parse::<Dither>().unwrap(), Dither::Atkinson);
+        assert_eq!("bayer".parse::<Dither>().unwrap(), Dither::Bayer);
+
+        assert!("threshold:300".parse::<Dither>().is_err());
+        assert!("threshold:-1".parse::<Dither>().is_err());
+        assert!("threshold:".parse::<Dither>().is_err());
+        assert!("bayer:4".parse::<Dither>().is_err());
+        assert!("sierra".parse::<Dither>().is_err());
+        assert!("".parse::<Dither>().is_err());
+    }
+
+    #[test]
+    pub fn only_screen_colours() {
+        let image = RgbaImage::from_fn(32, 32, |x, y| {
+            Rgba([(x * 8) as u8, (y * 8) as u8, ((x + y) * 4) as u8, (x * y % 256) as u8])
+        });
+        for dither in ALL {
+            let image = dithered(dither, image.clone());
+            assert!(
+                image.pixels().all(|pixel| [BLACK, WHITE, CLEAR].contains(pixel)),
+                "{dither:?}"
+            );
+        }
+    }
+
+    #[test]
+    pub fn alpha_threshold() {
+        let image = RgbaImage::from_fn(4, 1, |x, _| Rgba([255, 255, 255, [0, 127, 128, 255][x as usize]]));
+        for dither in ALL {
+            let image = dithered(dither, image.clone());
+            let pixels: Vec<_> = image.pixels().copied().collect();
+            assert_eq!(pixels, [CLEAR, CLEAR, WHITE, WHITE], "{dither:?}");
+        }
+    }
+
+    #[test]
+    pub fn threshold_boundary() {
+        let black = RgbaImage::from_pixel(1, 1, gray(0));
+        assert_eq!(dithered(Dither::Threshold(0), black.clone())[(0, 0)], WHITE);
+        assert_eq!(dithered(Dither::Threshold(1), black)[(0, 0)], BLACK);
+
+        // pixels at least as light as the threshold are white
+        let value = luma(&gray(100)).floor() as u8;
+        let image = RgbaImage::from_pixel(1, 1, gray(100));
+        assert_eq!(dithered(Dither::Threshold(value), image.clone())[(0, 0)], WHITE);
+        assert_eq!(dithered(Dither::Threshold(value + 1), image)[(0, 0)], BLACK);
+    }
+
+    #[test]
+    pub fn threshold_doesnt_diffuse() {
+        let image = RgbaImage::from_fn(16, 16, |x, _| if x < 8 { gray(120) } else { gray(136) });
+        let image = dithered(Dither::default(), image);
+        assert!(image.enumerate_pixels().all(|(x, _, pixel)| *pixel == if x < 8 { BLACK } else { WHITE }));
+    }
+
+    #[test]
+    pub fn bayer_mid_gray() {
+        let image = dithered(Dither::Bayer, RgbaImage::from_pixel(8, 8, gray(128)));
+        assert_eq!(white_fraction(&image), 0.5);
+        // alternating rows and columns, not a solid block
+        assert_ne!(image[(0, 0)], image[(1, 0)]);
+        assert_ne!(image[(0, 0)], image[(0, 1)]);
+
+        assert_eq!(white_fraction(&dithered(Dither::Bayer, RgbaImage::from_pixel(8, 8, gray(0)))), 0.0);
+        assert_eq!(white_fraction(&dithered(Dither::Bayer, RgbaImage::from_pixel(8, 8, gray(255)))), 1.0);
+    }
+
+    #[test]
+    pub fn error_diffusion_keeps_tone() {
+        for dither in [Dither::FloydSteinberg, Dither::Atkinson] {
+            let fraction = white_fraction(&dithered(dither, RgbaImage::from_pixel(16, 16, gray(127))));
+            assert!((0.4..=0.6).contains(&fraction), "{dither:?}: {fraction}");
+
+            // Atkinson drops some of the error, so dark grays come out darker
+            let fraction = white_fraction(&dithered(dither, RgbaImage::from_pixel(16, 16, gray(64))));
+            assert!((0.1..=0.3).contains(&fraction), "{dither:?}: {fraction}");
+
+            assert_eq!(white_fraction(&dithered(dither, RgbaImage::from_pixel(16, 16, gray(0)))), 0.0);
+            assert_eq!(white_fraction(&dithered(dither, RgbaImage::from_pixel(16, 16, gray(255)))), 1.0);
+        }
+    }
+
+    #[test]
+    pub fn settings_from_manifest() {
+        let manifest: DocumentMut = r#"
+            [dither]
+            "*" = "threshold:100"
+            "maps/./background.png" = "atkinson"
+        "#
+        .parse()
+        .unwrap();
+        let settings = DitherSettings::from_manifest(&manifest).unwrap();
+        assert_eq!(settings.default, Dither::Threshold(100));
+        assert_eq!(settings.for_image(Path::new("maps/background.png")), Dither::Atkinson);
+        assert_eq!(settings.for_image(Path::new("player.png")), Dither::Threshold(100));
+
+        let settings = DitherSettings::from_manifest(&DocumentMut::new()).unwrap();
+        assert_eq!(settings.default, Dither::default());
+        assert!(settings.images.is_empty());
+
+        let manifest: DocumentMut = "[dither]\n\"player.png\" = 3".parse().unwrap();
+        assert!(DitherSettings::from_manifest(&manifest).is_err());
+        let manifest: DocumentMut = "[dither]\n\"player.png\" = \"threshold:300\"".parse().unwrap();
+        assert!(DitherSettings::from_manifest(&manifest).is_err());
+    }
+}
@@ -0,0 +1,519 @@
+//! `editor dump <file> [--json]`: prints the contents of an exported archive.
+//!
+//! The archive is decoded and validated the same way the game does it (header, blocks, checksum,
+//! then [`OwnedArchived::new`]), so a file that dumps fine will also load on the device.
+
+use anyhow::{Context, bail};
+use hashbrown::HashSet;
+use pd_asset::AddDependencies;
+use pd_asset::RkyvError;
+use pd_asset::archive::{AlignVec, OwnedArchived};
+use pd_asset::block::Blocks;
+use pd_asset::bundle::{ArchivedBundleIndex, BUNDLE_EXTENSION, BundleHeader};
+use pd_asset::gif::ArchivedGif;
+use pd_asset::header::{ArchiveKind, AssetHeader, AssetKind};
+use pd_asset::properties::{ArchivedColor, ArchivedProperties, ArchivedPropertyValue};
+use pd_asset::rkyv::Portable;
+use pd_asset::rkyv::api::high::HighValidator;
+use pd_asset::rkyv::bytecheck::CheckBytes;
+use pd_asset::rkyv::primitive::ArchivedF32;
+use pd_asset::rkyv::tuple::ArchivedTuple2;
+use pd_asset::tilemap::{
+    ArchivedLayer, ArchivedLayerCollision, ArchivedLayerData, ArchivedObjectData,
+    ArchivedObjectShape, ArchivedTileStorage, ArchivedTilemap, WideTile,
+};
+use pd_asset::tileset::ArchivedTileset;
+use pd_asset::world::ArchivedWorld;
+use serde_json::{Map, Value, json};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Arrays of plain values longer than this are summarised in the tree output.
+const TREE_ARRAY_LIMIT: usize = 16;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DumpFormat {
+    Tree,
+    Json,
+}
+
+/// Parses the arguments after `dump` and prints the archive.
+pub fn run(args: &[String]) -> anyhow::Result<()> {
+    let mut format = DumpFormat::Tree;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => format = DumpFormat::Json,
+            "--tree" => format = DumpFormat::Tree,
+            _ if path.is_none() => path = Some(arg),
+            _ => bail!("unexpected argument {arg:?}\nusage: editor dump <file> [--json | --tree]"),
+        }
+    }
+    let Some(path) = path else {
+        bail!("usage: editor dump <file> [--json | --tree]");
+    };
+
+    let value = dump(Path::new(path))?;
+    match format {
+        DumpFormat::Json => println!("{}", serde_json::to_string_pretty(&value)?),
+        DumpFormat::Tree => print!("{}", tree(&value)),
+    }
+
+    Ok(())
+}
+
+/// Reads the archive (or bundle) at `path` and returns its contents.
+pub fn dump(path: &Path) -> anyhow::Result<Value> {
+    let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+    if path.extension().is_some_and(|ext| ext == BUNDLE_EXTENSION) {
+        return dump_bundle(&bytes).with_context(|| path.display().to_string());
+    }
+
+    let (header, archive) = decode(&bytes).with_context(|| path.display().to_string())?;
+    let value = match header.kind {
+        AssetKind::Tilemap => {
+            let map = OwnedArchived::<ArchivedTilemap>::new(archive)?;
+            with_dependencies(dump_tilemap(map.access()), map.access())
+        }
+        AssetKind::Tileset => {
+            let tileset = OwnedArchived::<ArchivedTileset>::new(archive)?;
+            with_dependencies(dump_tileset(tileset.access()), tileset.access())
+        }
+        AssetKind::Gif => {
+            let gif = OwnedArchived::<ArchivedGif>::new(archive)?;
+            with_dependencies(dump_gif(gif.access()), gif.access())
+        }
+        AssetKind::World => {
+            let world = OwnedArchived::<ArchivedWorld>::new(archive)?;
+            with_dependencies(dump_world(world.access()), world.access())
+        }
+    };
+
+    Ok(json!({
+        "kind": header.kind.name(),
+        "schema_version": header.schema_version,
+        "uncompressed_len": header.uncompressed_len,
+        "checksum": format!("{:#010x}", header.checksum),
+        "archive": value,
+    }))
+}
+
+/// Reads the archive at `path`, checking that it holds a `T`.
+pub fn load_archive<T>(path: &Path) -> anyhow::Result<OwnedArchived<T>>
+where
+    T: ArchiveKind + Portable + for<'a> CheckBytes<HighValidator<'a, RkyvError>>,
+{
+    let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let (header, archive) = decode(&bytes).with_context(|| path.display().to_string())?;
+    header
+        .expect(T::KIND)
+        .with_context(|| path.display().to_string())?;
+
+    Ok(OwnedArchived::new(archive)?)
+}
+
+/// Checks the header against the current schema and decompresses the archive bytes after it.
+pub fn decode(bytes: &[u8]) -> anyhow::Result<(AssetHeader, AlignVec)> {
+    let header = AssetHeader::from_bytes(bytes)?;
+    header.expect(header.kind)?;
+
+    let len = header.uncompressed_len as usize;
+    let mut aligned = AlignVec::with_capacity(len);
+    aligned.resize(len, 0);
+
+    for block in Blocks::new(&bytes[AssetHeader::SIZE..], len) {
+        let block = block?;
+        let out = &mut aligned[block.out.clone()];
+        if block.stored {
+            out.copy_from_slice(block.data);
+        } else {
+            let n = lz4_flex::block::decompress_into(block.data, out)?;
+            if n != out.len() {
+                bail!("block decompressed to {n} bytes, expected {}", out.len());
+            }
+        }
+    }
+
+    header.verify(&aligned)?;
+
+    Ok((header, aligned))
+}
+
+fn with_dependencies(mut value: Value, archive: &impl AddDependencies) -> Value {
+    let mut dependencies = HashSet::new();
+    archive.add_dependencies(&mut dependencies);
+    let mut dependencies: Vec<&str> = dependencies.into_iter().collect();
+    dependencies.sort_unstable();
+
+    value["dependencies"] = json!(dependencies);
+    value
+}
+
+fn dump_bundle(bytes: &[u8]) -> anyhow::Result<Value> {
+    let header = BundleHeader::from_bytes(bytes)?;
+    let index = bytes
+        .get(BundleHeader::SIZE..header.payload_start() as usize)
+        .context("bundle index is truncated")?;
+    let mut aligned = AlignVec::with_capacity(index.len());
+    aligned.extend_from_slice(index);
+    let index = OwnedArchived::<ArchivedBundleIndex>::new(aligned)?;
+
+    let mut entries: Vec<_> = index.access().entries.iter().collect();
+    entries.sort_unstable_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+    let entries: Map<String, Value> = entries
+        .into_iter()
+        .map(|(path, entry)| {
+            let entry = json!({
+                "offset": entry.offset.to_native(),
+                "len": entry.len.to_native(),
+                "compression": format!("{:?}", entry.compression),
+                "uncompressed_len": entry.uncompressed_len.to_native(),
+            });
+            (path.to_string(), entry)
+        })
+        .collect();
+
+    Ok(json!({
+        "kind": "bundle",
+        "schema_version": header.schema_version,
+        "entries": entries,
+    }))
+}
+
+fn dump_tilemap(map: &ArchivedTilemap) -> Value {
+    json!({
+        "tile_width": map.tile_width.to_native(),
+        "tile_height": map.tile_height.to_native(),
+        "width": map.width.to_native(),
+        "height": map.height.to_native(),
+        "infinite": map.infinite,
+        "orientation": format!("{:?}", map.orientation),
+        "background_color": map.background_color.as_ref().map(hex_color),
+        "tile_encoding": format!("{:?}", map.tile_encoding),
+        "tilesets": map.tilesets.iter().map(|tileset| tileset.as_str()).collect::<Vec<_>>(),
+        "properties": dump_properties(&map.properties),
+        "layers": map.layers.iter().map(dump_layer).collect::<Vec<_>>(),
+    })
+}
+
+fn dump_layer(layer: &ArchivedLayer) -> Value {
+    let mut value = json!({
+        "id": layer.id.to_native(),
+        "name": layer.name.as_str(),
+        "x": layer.x.to_native(),
+        "y": layer.y.to_native(),
+        "parallax": [layer.parallax_x.to_native(), layer.parallax_y.to_native()],
+        "opacity": layer.opacity.to_native(),
+        "tint_color": layer.tint_color.as_ref().map(hex_color),
+        "visible": layer.visible,
+        "properties": dump_properties(&layer.properties),
+    });
+
+    value["data"] = match &layer.layer_data {
+        ArchivedLayerData::FiniteTileLayer(tiles) => json!({
+            "type": "tiles",
+            "width": tiles.width.to_native(),
+            "height": tiles.height.to_native(),
+            "image": tiles.image.as_ref().map(|image| image.as_str()),
+            "collision": tiles.layer_collision.as_ref().map(dump_collision),
+            "tiles": dump_tiles(&tiles.tiles),
+        }),
+        ArchivedLayerData::InfiniteTileLayer(tiles) => {
+            let mut chunks: Vec<_> = tiles.chunk_data().collect();
+            chunks.sort_unstable_by_key(|(pos, _)| *pos);
+            let chunks: Vec<Value> = chunks
+                .into_iter()
+                .map(|((x, y), chunk)| {
+                    json!({
+                        "x": x,
+                        "y": y,
+                        "image": chunk.image.as_ref().map(|image| image.as_str()),
+                        "collision": chunk.collision.as_ref().map(dump_collision),
+                        "tiles": dump_tiles(&chunk.tiles),
+                    })
+                })
+                .collect();
+            json!({ "type": "infinite tiles", "chunks": chunks })
+        }
+        ArchivedLayerData::ObjectLayer(objects) => json!({
+            "type": "objects",
+            "objects": objects.objects.iter().map(dump_object).collect::<Vec<_>>(),
+        }),
+        ArchivedLayerData::ImageLayer(image) => json!({
+            "type": "image",
+            "source": image.source.as_str(),
+            "width": image.width.to_native(),
+            "height": image.height.to_native(),
+            "repeat_x": image.repeat_x,
+            "repeat_y": image.repeat_y,
+        }),
+        ArchivedLayerData::GroupLayer(group) => json!({
+            "type": "group",
+            "layers": group.layers.iter().map(dump_layer).collect::<Vec<_>>(),
+        }),
+    };
+
+    value
+}
+
+/// Every tile as `"<tileset index>:<tile id>"` followed by its flips, or `null` if empty.
+fn dump_tiles(tiles: &ArchivedTileStorage) -> Value {
+    fn tile(tile: WideTile) -> String {
+        let mut out = format!("{}:{}", tile.get_tilemap_idx(), tile.tile_id());
+        for (flipped, flag) in [
+            (tile.get_flip_x(), 'X'),
+            (tile.get_flip_y(), 'Y'),
+            (tile.get_flip_d(), 'D'),
+        ] {
+            if flipped {
+                out.push(flag);
+            }
+        }
+        out
+    }
+
+    json!({
+        "encoding": format!("{:?}", tiles.encoding()),
+        "count": tiles.iter().flatten().count(),
+        "tiles": tiles.iter().map(|t| t.map(tile)).collect::<Vec<_>>(),
+    })
+}
+
+fn dump_collision(collision: &ArchivedLayerCollision) -> Value {
+    let segments: usize = collision
+        .lines
+        .iter()
+        .map(|line| line.len().saturating_sub(1))
+        .sum();
+    let lines: Vec<Vec<[f32; 2]>> = collision.lines.iter().map(|line| points(line)).collect();
+
+    json!({
+        "line_count": lines.len(),
+        "segment_count": segments,
+        "lines": lines,
+    })
+}
+
+fn points(points: &[ArchivedTuple2<ArchivedF32, ArchivedF32>]) -> Vec<[f32; 2]> {
+    points
+        .iter()
+        .map(|point| [point.0.to_native(), point.1.to_native()])
+        .collect()
+}
+
+fn dump_object(object: &ArchivedObjectData) -> Value {
+    let shape = match &object.shape {
+        ArchivedObjectShape::Tile {
+            tile,
+            width,
+            height,
+        } => json!({
+            "type": "tile",
+            "tileset": tile.get_tilemap_idx(),
+            "tile_id": tile.tile_id(),
+            "flip_x": tile.get_flip_x(),
+            "flip_y": tile.get_flip_y(),
+            "width": width.to_native(),
+            "height": height.to_native(),
+        }),
+        ArchivedObjectShape::Rect { width, height } => json!({
+            "type": "rect",
+            "width": width.to_native(),
+            "height": height.to_native(),
+        }),
+        ArchivedObjectShape::Ellipse { width, height } => json!({
+            "type": "ellipse",
+            "width": width.to_native(),
+            "height": height.to_native(),
+        }),
+        ArchivedObjectShape::Polyline { points: line } => json!({
+            "type": "polyline",
+            "points": points(line),
+        }),
+        ArchivedObjectShape::Polygon { points: polygon } => json!({
+            "type": "polygon",
+            "points": points(polygon),
+        }),
+        ArchivedObjectShape::Point(x, y) => json!({
+            "type": "point",
+            "x": x.to_native(),
+            "y": y.to_native(),
+        }),
+        ArchivedObjectShape::Text(text) => json!({
+            "type": "text",
+            "text": text.text.as_str(),
+            "font": text.font.as_ref().map(|font| font.as_str()),
+            "pixel_size": text.pixel_size.to_native(),
+            "wrap": text.wrap,
+            "halign": format!("{:?}", text.halign),
+            "valign": format!("{:?}", text.valign),
+            "width": text.width.to_native(),
+            "height": text.height.to_native(),
+        }),
+    };
+
+    json!({
+        "id": object.id.to_native(),
+        "name": object.name.as_str(),
+        "x": object.x.to_native(),
+        "y": object.y.to_native(),
+        "rotation": object.rotation.to_native(),
+        "visible": object.visible,
+        "shape": shape,
+        "properties": dump_properties(&object.properties),
+    })
+}
+
+/// Formats a color the same as Tiled, `#AARRGGBB`.
+fn hex_color(color: &ArchivedColor) -> String {
+    format!(
+        "#{:02x}{:02x}{:02x}{:02x}",
+        color.alpha, color.red, color.green, color.blue
+    )
+}
+
+fn dump_properties(properties: &ArchivedProperties) -> Value {
+    let properties: Map<String, Value> = properties
+        .iter()
+        .map(|(name, value)| (name.to_string(), dump_property(value)))
+        .collect();
+    Value::Object(properties)
+}
+
+fn dump_property(value: &ArchivedPropertyValue) -> Value {
+    match value {
+        ArchivedPropertyValue::BoolValue(value) => json!(value),
+        ArchivedPropertyValue::FloatValue(value) => json!(value.to_native()),
+        ArchivedPropertyValue::IntValue(value) => json!(value.to_native()),
+        ArchivedPropertyValue::ColorValue(color) => json!(hex_color(color)),
+        ArchivedPropertyValue::StringValue(value) => json!(value.as_str()),
+        ArchivedPropertyValue::FileValue(path) => json!({ "file": path.as_str() }),
+        ArchivedPropertyValue::ObjectValue(id) => json!({ "object": id.to_native() }),
+        ArchivedPropertyValue::ClassValue {
+            property_type,
+            properties,
+        } => json!({
+            "class": property_type.as_str(),
+            "properties": dump_properties(properties),
+        }),
+    }
+}
+
+fn dump_tileset(tileset: &ArchivedTileset) -> Value {
+    // most tiles have neither properties nor an animation
+    let tiles: Vec<Value> = tileset
+        .tiles
+        .iter()
+        .enumerate()
+        .filter(|(_, tile)| !tile.properties.is_empty() || tile.animation.is_some())
+        .map(|(id, tile)| {
+            let animation = tile.animation.as_ref().map(|frames| {
+                frames
+                    .iter()
+                    .map(|frame| {
+                        json!({
+                            "tile_id": frame.tile_id.to_native(),
+                            "duration": frame.duration.to_native(),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            });
+            json!({
+                "id": id,
+                "properties": dump_properties(&tile.properties),
+                "animation": animation,
+            })
+        })
+        .collect();
+
+    json!({
+        "image_path": tileset.image_path.as_str(),
+        "tile_count": tileset.tiles.len(),
+        "tiles": tiles,
+    })
+}
+
+fn dump_gif(gif: &ArchivedGif) -> Value {
+    json!({
+        "image_path": gif.image_path.as_str(),
+        "fps": gif.fps.to_native(),
+    })
+}
+
+fn dump_world(world: &ArchivedWorld) -> Value {
+    let maps: Vec<Value> = world
+        .maps
+        .iter()
+        .map(|map| {
+            json!({
+                "path": map.path.as_str(),
+                "x": map.x.to_native(),
+                "y": map.y.to_native(),
+                "width": map.width.to_native(),
+                "height": map.height.to_native(),
+            })
+        })
+        .collect();
+
+    json!({ "maps": maps })
+}
+
+/// Renders a value as an indented tree. Long arrays of plain values (tiles, points) are
+/// summarised, use `--json` to see all of them.
+pub fn tree(value: &Value) -> String {
+    fn is_leaf(value: &Value) -> bool {
+        match value {
+            Value::Array(items) => items.iter().all(|item| !item.is_object() && is_leaf(item)),
+            Value::Object(_) => false,
+            _ => true,
+        }
+    }
+
+    fn leaf(value: &Value) -> String {
+        match value {
+            Value::Array(items) if items.len() > TREE_ARRAY_LIMIT => {
+                format!("[{} items]", items.len())
+            }
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(leaf).collect();
+                format!("[{}]", items.join(", "))
+            }
+            Value::String(s) => format!("{s:?}"),
+            value => value.to_string(),
+        }
+    }
+
+    fn node(out: &mut String, depth: usize, label: &str, value: &Value) {
+        let indent = "  ".repeat(depth);
+        match value {
+            Value::Null => {}
+            _ if is_leaf(value) => writeln!(out, "{indent}{label}: {}", leaf(value)).unwrap(),
+            Value::Object(fields) if fields.is_empty() => {}
+            Value::Object(fields) => {
+                writeln!(out, "{indent}{label}:").unwrap();
+                for (name, field) in fields {
+                    node(out, depth + 1, name, field);
+                }
+            }
+            Value::Array(items) => {
+                writeln!(out, "{indent}{label}: ({} items)", items.len()).unwrap();
+                for (i, item) in items.iter().enumerate() {
+                    node(out, depth + 1, &format!("[{i}]"), item);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let mut out = String::new();
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields {
+                node(&mut out, 0, name, field);
+            }
+        }
+        value => node(&mut out, 0, "value", value),
+    }
+    out
+}
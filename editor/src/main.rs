@@ -1,7 +1,14 @@
+mod aseprite;
+mod cache;
+mod dither;
+mod dump;
 mod pdtiled;
+mod validate;
 
-use crate::pdtiled::{convert_map, convert_tileset};
+use crate::cache::{BuildCache, Keys};
+use crate::pdtiled::{convert_map, convert_tileset, convert_world};
 use indexmap::IndexSet;
+use std::collections::{HashMap, HashSet};
 use regex::Regex;
 use std::ffi::OsStr;
 use std::fs;
@@ -10,122 +17,526 @@ use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::str::FromStr;
-use std::sync::LazyLock;
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::{LazyLock, OnceLock};
+use std::time::{Duration, SystemTime};
+use anyhow::{Context, bail};
 use gif::{DisposalMethod, ExtensionData, Repeat};
 use image::{GenericImage, Rgb, Rgba, RgbaImage};
 use pd_asset::dependencies::AddDependenciesMut;
 use toml_edit::{Item, Table, value};
 use pd_asset::gif::Gif;
+use pd_asset::bundle::{BundleEntry, BundleHeader, BundleIndex, Compression, BUNDLE_EXTENSION};
+use pd_asset::block::{block_prefix, BLOCK_SIZE};
+use pd_asset::header::{AssetHeader, AssetKind};
+
+const USAGE: &str = "\
+usage: editor [options] <command>
+
+commands:
+  build [--bump]              export the assets in the manifest and list them in game/Cargo.toml,
+                              incrementing the build number with --bump
+  clean                       delete the export folder, build cache and image previews, so
+                              the next build converts everything
+  dump <file> [--json]        print the contents of an exported archive or bundle
+  to-tiled <file> [--out dir] convert an exported map or tileset back into Tiled files
+  watch                       build, then build again whenever an asset changes
+  run --simulator|--device    build, then run the game
+
+options:
+  --assets <dir>              folder the assets of the manifest are in (default: assets)
+  --manifest <file>           asset manifest (default: manifest.toml)
+  --export <dir>              folder assets are exported to (default: <assets>/export)
+  --no-validate               don't check properties against the types the game exported";
+
+/// Cargo manifest of the game, where the exported assets are listed.
+const GAME_TOML: &str = "game/Cargo.toml";
 
 fn main() -> anyhow::Result<()> {
-    let game_toml = std::fs::read_to_string("game/Cargo.toml")?;
+    let mut args = std::env::args().skip(1);
+    let mut assets = PathBuf::from(ASSET_PATH);
+    let mut manifest = PathBuf::from("manifest.toml");
+    let mut export = None;
+    let mut validate = true;
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--assets" => assets = args.next().context(USAGE)?.into(),
+            "--manifest" => manifest = args.next().context(USAGE)?.into(),
+            "--export" => export = Some(PathBuf::from(args.next().context(USAGE)?)),
+            "--no-validate" => validate = false,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => rest.push(arg),
+        }
+    }
+    let export = export.unwrap_or_else(|| assets.join(EXPORT_FOLDER));
+    PATHS
+        .set(Paths {
+            assets: path::normalize(&assets),
+            manifest,
+            export: path::normalize(&export),
+            validate,
+        })
+        .unwrap();
+
+    let Some((command, args)) = rest.split_first() else {
+        bail!("{USAGE}");
+    };
+    match command.as_str() {
+        "build" => match args {
+            [] => build(false),
+            [bump] if bump == "--bump" => build(true),
+            _ => bail!("unexpected arguments {args:?}\n{USAGE}"),
+        },
+        "clean" => match args {
+            [] => clean(),
+            _ => bail!("unexpected arguments {args:?}\n{USAGE}"),
+        },
+        "dump" => dump::run(args),
+        "to-tiled" => pdtiled::reverse::run(args),
+        "watch" => match args {
+            [] => watch(),
+            _ => bail!("unexpected arguments {args:?}\n{USAGE}"),
+        },
+        "run" => {
+            let device = match args {
+                [target] if target == "--simulator" => false,
+                [target] if target == "--device" => true,
+                _ => bail!("run needs one of --simulator or --device\n{USAGE}"),
+            };
+            build(false)?;
+            run_game(device)
+        }
+        _ => bail!("unknown command {command:?}\n{USAGE}"),
+    }
+}
+
+/// Where the editor reads and writes files on this computer, set once from the command line.
+#[derive(Debug)]
+pub struct Paths {
+    /// Folder the assets listed in the manifest are in.
+    pub assets: PathBuf,
+    pub manifest: PathBuf,
+    /// Folder the processed assets are written to.
+    pub export: PathBuf,
+    /// Whether properties are checked against the types the game exported.
+    pub validate: bool,
+}
+
+static PATHS: OnceLock<Paths> = OnceLock::new();
+
+pub fn paths() -> &'static Paths {
+    PATHS.get().expect("paths are set at startup")
+}
+
+/// Exports every asset, then lists the exported files in the game's Cargo manifest so
+/// `cargo playdate` packages them.
+///
+/// The build goes to a copy of the export folder, which only replaces it (just before the
+/// manifest is rewritten) if every asset converted, so a failed build leaves the last good one.
+fn build(bump: bool) -> anyhow::Result<()> {
+    let game_toml = fs::read_to_string(GAME_TOML).with_context(|| GAME_TOML.to_string())?;
     let mut game_toml = toml_edit::DocumentMut::from_str(&game_toml)?;
     let playdate = &mut game_toml["package"]["metadata"]["playdate"];
-    // increment build number
-    {
+    if bump {
         println!("incrementing build number");
         let build_number = &mut playdate["build-number"];
         *build_number = value(build_number.as_integer().unwrap() + 1);
     }
-    // process assets
-    {
-        println!("processing assets");
-        let mut asset_table = Table::new();
-
-        let _ = fs::remove_dir_all("assets\\export");
-
-        let _ = run_assets();
-
-        // get all files in export folder (recursively)
-        let mut files = Vec::new();
-        let mut directories = vec![PathBuf::from("assets\\export")];
-        while let Some(directory) = directories.pop() {
-            for file in fs::read_dir(&directory)? {
-                let file = file?;
-                let metadata = file.metadata()?;
-                if metadata.is_dir() {
-                    directories.push(file.path());
-                } else if metadata.is_file() {
-                    files.push(file.path());
-                }
-            }
-        }
 
-        for file in files {
-            let file = file.to_string_lossy().replace("\\", "/");
-            let source = format!("../{}", file);
-            let destination = file.replacen("/export", "", 1);
-            asset_table.insert(&destination, source.into());
-        }
+    println!("processing assets");
+    let export = &paths().export;
+    let staging = sibling_dir(export, "staging");
+    remove_dir(&staging)?;
+    if export.is_dir() {
+        copy_dir(export, &staging)?;
+    }
+    if let Err(err) = run_assets(&staging) {
+        remove_dir(&staging)?;
+        return Err(err);
+    }
 
-        // fs::read_di
-        // for asset in assets {
-        //     let asset = asset.to_string_lossy().to_string();
-        //     let destination = format!("assets\\{}", asset);
-        //     let source = format!("../{}/{}/{}", ASSET_PATH, EXPORT_FOLDER, asset);
-        //     asset_table.insert(&destination, source.into());
-        // }
+    let mut asset_table = Table::new();
+    for file in files_in(&staging)? {
+        if file == BuildCache::path(&staging) {
+            continue;
+        }
+        let relative = file.strip_prefix(&staging)?;
+        let destination = format!("{ASSET_PATH}/{}", path::to_slash(relative));
+        // where it will be once swapped in, relative to the game crate
+        let file = export.join(relative);
+        let source = if file.is_absolute() {
+            file
+        } else {
+            Path::new("..").join(&file)
+        };
+        asset_table.insert(&destination, path::to_slash(&source).into());
+    }
+    playdate["assets"] = Item::Table(asset_table);
 
-        playdate["assets"] = Item::Table(asset_table);
+    let old = sibling_dir(export, "old");
+    remove_dir(&old)?;
+    if export.exists() {
+        fs::rename(export, &old)?;
     }
+    fs::rename(&staging, export)?;
+    remove_dir(&old)?;
+
+    let game_toml_tmp = Path::new(GAME_TOML).with_extension("toml.tmp");
+    fs::write(&game_toml_tmp, game_toml.to_string())?;
+    fs::rename(&game_toml_tmp, GAME_TOML)?;
+
+    Ok(())
+}
 
-    std::fs::write("game/Cargo.toml", game_toml.to_string())?;
+/// Deletes the export folder, and with it the build cache, and the image previews.
+fn clean() -> anyhow::Result<()> {
+    let export = &paths().export;
+    remove_dir(&sibling_dir(export, "staging"))?;
+    remove_dir(&preview_dir())?;
+    remove_dir(export)
+}
 
-    // dbg!(generate_transition());
+/// `directory` with `suffix` added to its name, e.g. `assets/export.staging`.
+fn sibling_dir(directory: &Path, suffix: &str) -> PathBuf {
+    let mut name = directory.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    directory.with_file_name(name)
+}
+
+/// Where previews of exported images are written, next to the export folder. Not part of the
+/// build, so they aren't packaged with the game.
+fn preview_dir() -> PathBuf {
+    sibling_dir(&paths().export, "preview")
+}
+
+/// Deletes `directory` and everything in it, if it exists.
+fn remove_dir(directory: &Path) -> anyhow::Result<()> {
+    match fs::remove_dir_all(directory) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            Err(err).with_context(|| directory.display().to_string())
+        }
+        _ => Ok(()),
+    }
+}
 
+/// Copies every file in `from` to the same place in `to`.
+fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    for file in files_in(from)? {
+        let target = to.join(file.strip_prefix(from)?);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&file, &target).with_context(|| file.display().to_string())?;
+    }
     Ok(())
-    // run_game(true)
 }
 
-pub fn run_game(device: bool) {
+/// Extensions of the files in the assets folder that start a build when they change.
+const WATCHED_EXTENSIONS: &[&str] = &["tmx", "tsx", "tx", "png", "world", "aseprite", "fnt"];
+
+/// Builds, then polls the assets folder and the manifest and builds again whenever they change.
+///
+/// Only the assets affected by the change are converted again, thanks to the build cache.
+fn watch() -> anyhow::Result<()> {
+    loop {
+        // a bad manifest panics, which shouldn't stop the watch
+        match std::panic::catch_unwind(|| build(false)) {
+            Ok(Ok(())) => println!("built, watching for changes"),
+            Ok(Err(err)) => eprintln!("build failed: {err:#}\nwatching for changes"),
+            Err(_) => eprintln!("build failed, watching for changes"),
+        }
+
+        // baked images are written to the assets folder, so only look for changes after building
+        let built = watched_files()?;
+        loop {
+            std::thread::sleep(Duration::from_millis(500));
+            let now = watched_files()?;
+            let changed: Vec<&PathBuf> = now
+                .iter()
+                .filter(|(file, modified)| built.get(*file) != Some(modified))
+                .map(|(file, _)| file)
+                .chain(built.keys().filter(|file| !now.contains_key(*file)))
+                .collect();
+            if changed.is_empty() {
+                continue;
+            }
+
+            for file in changed {
+                println!("changed {}", path::to_slash(file));
+            }
+            // give Tiled time to finish writing everything it saves at once
+            std::thread::sleep(Duration::from_millis(200));
+            break;
+        }
+    }
+}
+
+/// The modification time of the manifest and of every watched file in the assets folder,
+/// leaving out exports.
+fn watched_files() -> anyhow::Result<HashMap<PathBuf, SystemTime>> {
+    let paths = paths();
+    let ignored = [
+        paths.export.clone(),
+        sibling_dir(&paths.export, "staging"),
+        sibling_dir(&paths.export, "old"),
+        preview_dir(),
+    ];
+
+    let mut files = HashMap::new();
+    let assets = files_in(&paths.assets)?.into_iter().filter(|file| {
+        let watched = file
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|extension| WATCHED_EXTENSIONS.contains(&extension));
+        watched && !ignored.iter().any(|dir| file.starts_with(dir))
+    });
+    for file in std::iter::once(paths.manifest.clone()).chain(assets) {
+        // the file may be gone for a moment if an editor is saving over it
+        if let Ok(modified) = fs::metadata(&file).and_then(|metadata| metadata.modified()) {
+            files.insert(file, modified);
+        }
+    }
+    Ok(files)
+}
+
+/// Every file in `directory`, recursively.
+fn files_in(directory: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut directories = vec![directory.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for file in fs::read_dir(&directory).with_context(|| directory.display().to_string())? {
+            let file = file?;
+            let metadata = file.metadata()?;
+            if metadata.is_dir() {
+                directories.push(file.path());
+            } else if metadata.is_file() {
+                files.push(file.path());
+            }
+        }
+    }
+    Ok(files)
+}
+
+pub fn run_game(device: bool) -> anyhow::Result<()> {
     let target = if device { "--device" } else { "--simulator" };
 
-    Command::new("cargo")
+    let status = Command::new("cargo")
         .args(["playdate", "run", "-p", "game", target])
         .arg("--release")
-        .spawn()
-        .unwrap()
-        .wait()
-        .unwrap();
+        .status()?;
+    if !status.success() {
+        bail!("cargo playdate run failed: {status}");
+    }
+
+    Ok(())
 }
 
-pub fn run_assets() -> Vec<PathBuf> {
-    let manifest = std::fs::read_to_string("manifest.toml").unwrap();
-    let manifest = toml_edit::DocumentMut::from_str(&manifest).unwrap();
-    let err = std::fs::remove_dir_all(Path::new(ASSET_PATH).join(EXPORT_FOLDER));
-    if let Err(err) = err {
-        if err.kind() != ErrorKind::NotFound {
-            panic!("{}", err);
+/// Converts every asset in the manifest (and everything they reference) into `export`,
+/// skipping those the build cache in `export` says are up to date. Prints a line per asset.
+///
+/// Fails if any asset failed to convert, after trying all of them.
+pub fn run_assets(export: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let manifest = std::fs::read_to_string(&paths().manifest)
+        .with_context(|| paths().manifest.display().to_string())?;
+    let manifest = toml_edit::DocumentMut::from_str(&manifest)?;
+
+    let mut assets = Assets {
+        export: export.to_path_buf(),
+        ..Assets::default()
+    };
+
+    // the simulator may not be installed at all when validation is off
+    let mut type_export = None;
+    if paths().validate {
+        let path = match manifest.get("type-export").and_then(Item::as_str) {
+            Some(path) => PathBuf::from(path),
+            None => validate::simulator_type_export()?,
+        };
+        assets.types = validate::TypeRegistry::load(&path)?;
+        if assets.types.is_none() {
+            bail!(
+                "{} not found, run the game in the simulator once to export its types, or build \
+                 with --no-validate",
+                path.display()
+            );
         }
+        type_export = Some(path);
+    } else {
+        println!("skipping property validation");
     }
 
-    let mut assets = Assets::default();
+    assets.dither = dither::DitherSettings::from_manifest(&manifest)?;
+
+    // before anything reads the sheets
+    aseprite::sync_sheets()?;
 
-    for asset in manifest["assets"].as_array().unwrap() {
-        let s = asset.as_str().unwrap().to_string();
+    let settings = manifest.get("dither").map(ToString::to_string).unwrap_or_default();
+    let version = cache::converter_version(type_export.as_deref(), &settings);
+    let cache = BuildCache::load(export, version);
+    let mut cached_keys = Keys::default();
+    // assets taken from the queue, converted or not, which go in the new cache
+    let mut built = Vec::new();
+
+    for asset in manifest["assets"].as_array().context("manifest has no assets array")? {
+        let s = asset.as_str().context("assets must be paths")?.to_string();
         let path = path::pd_to_pc(s);
         assets.add_asset(path, true);
     }
 
+    let mut failed = 0;
     while let Some(asset) = assets.fulfill_next() {
-        println!("↳{:?}", asset);
-        let extension = asset.extension();
-        if extension == Some(OsStr::new("tmx")) || extension == Some(OsStr::new("tmb")) {
-            process_map(&asset, &mut assets);
-        } else if extension == Some(OsStr::new("tsx")) || extension == Some(OsStr::new("tsb")) {
-            process_tileset(&asset, &mut assets);
-        } else {
-            process_default(&asset);
+        built.push(asset.clone());
+        let name = path::to_slash(&asset);
+        if let Some(entry) = cache.fresh(&asset, &mut cached_keys, export) {
+            println!("  up to date  {name}");
+            assets.reuse(&asset, entry);
+            continue;
         }
+
+        let errors = assets.property_errors.len();
+        // the converters panic on bad assets, report which one and carry on with the rest
+        let result =
+            std::panic::catch_unwind(AssertUnwindSafe(|| process_asset(&asset, &mut assets)));
+        match result {
+            Ok(()) if assets.property_errors.len() == errors => println!("  converted   {name}"),
+            Ok(()) => {
+                failed += 1;
+                println!("  invalid     {name}");
+                for error in &assets.property_errors[errors..] {
+                    eprintln!("{error}");
+                }
+            }
+            Err(panic) => {
+                failed += 1;
+                println!("  failed      {name}: {}", panic_message(&*panic));
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{failed} assets failed to convert, see above");
+    }
+
+    process_transition(&mut assets);
+
+    write_bundles(&manifest, &mut assets);
+
+    // keys are computed from the dependencies found this build, now that every asset has been
+    // converted (maps write the images of baked layers)
+    let mut keys = Keys::default();
+    let dependencies = |asset: &Path| {
+        assets
+            .dependencies
+            .get(asset)
+            .map(|dependencies| dependencies.iter().cloned().collect())
+            .unwrap_or_default()
+    };
+    let sources = |asset: &Path| assets.sources.get(asset).cloned().unwrap_or_default();
+    let keyed = |asset: &Path| {
+        let mut keyed = dependencies(asset);
+        keyed.extend(sources(asset));
+        keyed
+    };
+    let entries = built
+        .into_iter()
+        .map(|asset| {
+            let entry = cache::CacheEntry {
+                key: keys.key(&asset, &keyed),
+                dependencies: dependencies(&asset),
+                sources: sources(&asset),
+                outputs: assets.outputs.get(&asset).cloned().unwrap_or_default(),
+            };
+            (asset, entry)
+        })
+        .collect();
+
+    remove_stale_exports(&assets)?;
+    BuildCache {
+        version: cache.version,
+        entries,
     }
+    .save(export)?;
+
+    Ok(assets.finish())
+}
+
+/// Converts (or copies) `asset` into the export folder depending on its extension.
+fn process_asset(asset: &Path, assets: &mut Assets) {
+    let extension = asset.extension();
+    if extension == Some(OsStr::new("tmx")) || extension == Some(OsStr::new("tmb")) {
+        process_map(asset, assets);
+    } else if extension == Some(OsStr::new("tsx")) || extension == Some(OsStr::new("tsb")) {
+        process_tileset(asset, assets);
+    } else if extension == Some(OsStr::new("world")) || extension == Some(OsStr::new("wdb")) {
+        process_world(asset, assets);
+    } else if extension == Some(OsStr::new("aseprite")) {
+        process_aseprite(asset, assets);
+    } else if extension == Some(OsStr::new("fnt")) {
+        process_font(asset, assets);
+    } else {
+        process_default(asset, assets);
+    }
+}
 
-    assets.finish()
+/// The message a panic was started with.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("panicked")
+}
+
+/// Deletes every file in the export folder that no asset exported this build, like the exports of
+/// assets removed from the manifest.
+fn remove_stale_exports(assets: &Assets) -> anyhow::Result<()> {
+    let export = &assets.export;
+    let Ok(files) = files_in(export) else {
+        return Ok(());
+    };
+    let outputs: HashSet<&PathBuf> = assets.outputs.values().flatten().collect();
+    for file in files {
+        let Ok(relative) = file.strip_prefix(export) else {
+            continue;
+        };
+        if file == BuildCache::path(export) || outputs.contains(&relative.to_path_buf()) {
+            continue;
+        }
+        match fs::remove_file(&file) {
+            // already removed since listing the folder
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            result => result.with_context(|| format!("removing {}", file.display()))?,
+        }
+        println!("  removed     {}", path::to_slash(relative));
+    }
+    Ok(())
 }
 
 #[derive(Default)]
 struct Assets {
     processed_assets: IndexSet<PathBuf>,
     assets_to_process: IndexSet<PathBuf>,
+    /// Assets each asset references, all relative to the `assets` folder.
+    dependencies: HashMap<PathBuf, IndexSet<PathBuf>>,
+    /// The types exported by the game, if it has exported them.
+    types: Option<validate::TypeRegistry>,
+    /// Properties the game would fail to load, reported once every asset is processed.
+    property_errors: Vec<validate::PropertyError>,
+    /// Files each asset exported, relative to the export folder.
+    outputs: HashMap<PathBuf, Vec<PathBuf>>,
+    /// Files besides its dependencies each asset was converted from, like the object templates
+    /// of a map, relative to the `assets` folder. They aren't exported themselves.
+    sources: HashMap<PathBuf, Vec<PathBuf>>,
+    /// Folder assets are exported to for this build.
+    export: PathBuf,
+    /// How images are quantized to black and white.
+    dither: dither::DitherSettings,
 }
 
 impl Assets {
@@ -157,19 +568,70 @@ impl Assets {
         self.processed_assets.get_index(i).cloned()
     }
 
+    /// Records that the asset at `origin` (a path **including** the `assets` folder, as passed to
+    /// [`process_asset_paths`]) references `dependency` (relative to the `assets` folder).
+    pub fn add_dependency(&mut self, origin: &Path, dependency: &Path) {
+        let origin = origin.strip_prefix(&paths().assets).unwrap_or(origin).to_path_buf();
+        self.dependencies
+            .entry(origin)
+            .or_default()
+            .insert(dependency.to_path_buf());
+    }
+
+    /// Records that `asset` exports `output` (both relative to their folders), returning where
+    /// to write it with its folder created.
+    pub fn export_path(&mut self, asset: &Path, output: PathBuf) -> PathBuf {
+        let export_path = self.export.join(&output);
+        if let Some(parent) = export_path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        self.outputs.entry(asset.to_path_buf()).or_default().push(output);
+        export_path
+    }
+
+    /// Keeps the exports of an up to date `asset` and queues what it references, as if it had
+    /// been converted.
+    pub fn reuse(&mut self, asset: &Path, entry: &cache::CacheEntry) {
+        for dependency in &entry.dependencies {
+            self.dependencies
+                .entry(asset.to_path_buf())
+                .or_default()
+                .insert(dependency.clone());
+            self.add_asset(dependency.clone(), true);
+        }
+        self.outputs.insert(asset.to_path_buf(), entry.outputs.clone());
+        self.sources.insert(asset.to_path_buf(), entry.sources.clone());
+    }
+
+    /// Returns `roots` and everything they reference, directly or not.
+    pub fn with_dependencies(&self, roots: impl IntoIterator<Item = PathBuf>) -> IndexSet<PathBuf> {
+        let mut out = IndexSet::new();
+        let mut to_visit: Vec<PathBuf> = roots.into_iter().collect();
+        while let Some(asset) = to_visit.pop() {
+            if let Some(dependencies) = self.dependencies.get(&asset) {
+                to_visit.extend(dependencies.iter().filter(|d| !out.contains(*d)).cloned());
+            }
+            out.insert(asset);
+        }
+        out
+    }
+
     pub fn finish(self) -> Vec<PathBuf> {
         self.processed_assets.into_iter().collect()
     }
 }
 
+/// Folder the exported assets are in on the Playdate, and the default assets folder.
 const ASSET_PATH: &str = "assets";
+/// Default export folder, inside the assets folder.
 const EXPORT_FOLDER: &str = "export";
-fn process_map(path: &Path, assets: &mut Assets) {
-    println!("processing tilemap: {:?}", path);
 
-    let true_map_path = Path::new(ASSET_PATH).join(path);
+fn process_map(path: &Path, assets: &mut Assets) {
+    let true_map_path = paths().assets.join(path);
 
     let map = tiled::Loader::new().load_tmx_map(&true_map_path).unwrap();
+    // object templates are baked into the map, so it has to be converted again when they change
+    assets.sources.insert(path.to_path_buf(), template_paths(&true_map_path));
     let mut map = convert_map(map);
 
     let mut asset_paths = Vec::new();
@@ -177,29 +639,37 @@ fn process_map(path: &Path, assets: &mut Assets) {
 
     process_asset_paths(assets, asset_paths, &true_map_path);
 
+    if let Some(types) = &assets.types {
+        let errors = types.check_tilemap(&true_map_path, &map);
+        if !errors.is_empty() {
+            assets.property_errors.extend(errors);
+            return;
+        }
+    }
+
     let bytes = pd_asset::rkyv::to_bytes::<pd_asset::RkyvError>(&map).unwrap();
     // dbg!(pd_asset::rkyv::access::<ArchivedTilemap, pd_asset::RkyvError>(&bytes).unwrap());
 
-    let bytes = lz4_flex::compress_prepend_size(&bytes);
+    let bytes = encode_archive(AssetKind::Tilemap, &bytes);
 
-    let mut path = path.to_path_buf();
-    path.set_extension("tmb");
-
-    let export_path = Path::new(ASSET_PATH).join(EXPORT_FOLDER).join(path);
-
-    if let Some(parent) = export_path.parent() {
-        std::fs::create_dir_all(parent).unwrap();
-    }
+    let export_path = assets.export_path(path, path.with_extension("tmb"));
     std::fs::write(export_path, &bytes).unwrap();
 }
 
 fn process_tileset(path: &Path, assets: &mut Assets) {
-    println!("processing tileset: {:?}", path);
-
-    let true_set_path = Path::new(ASSET_PATH).join(path);
+    let true_set_path = paths().assets.join(path);
     let tileset = tiled::Loader::new()
         .load_tsx_tileset(&true_set_path)
         .unwrap();
+
+    let mut errors = Vec::new();
+    if let Some(types) = &assets.types {
+        for (id, tile) in tileset.tiles() {
+            let properties = pdtiled::convert_properties(tile.properties.clone());
+            errors.extend(types.check_tile(&true_set_path, id, &properties));
+        }
+    }
+
     let mut tileset = convert_tileset(tileset);
 
     let mut asset_paths = Vec::new();
@@ -207,59 +677,210 @@ fn process_tileset(path: &Path, assets: &mut Assets) {
 
     process_asset_paths(assets, asset_paths, &true_set_path);
 
+    if !errors.is_empty() {
+        assets.property_errors.extend(errors);
+        return;
+    }
+
     let bytes = pd_asset::rkyv::to_bytes::<pd_asset::RkyvError>(&tileset).unwrap();
     // dbg!(pd_asset::rkyv::access::<ArchivedTileset, pd_asset::RkyvError>(&bytes).unwrap());
 
-    let bytes = lz4_flex::compress_prepend_size(&bytes);
+    let bytes = encode_archive(AssetKind::Tileset, &bytes);
 
-    let mut path = path.to_path_buf();
-    path.set_extension("tsb");
+    let export_path = assets.export_path(path, path.with_extension("tsb"));
+    std::fs::write(export_path, &bytes).unwrap();
+}
 
-    let export_path = Path::new(ASSET_PATH).join(EXPORT_FOLDER).join(path);
+fn process_world(path: &Path, assets: &mut Assets) {
+    let true_world_path = paths().assets.join(path);
+    let world = tiled::Loader::new().load_world(&true_world_path).unwrap();
+    let mut world = convert_world(world);
 
-    if let Some(parent) = export_path.parent() {
-        std::fs::create_dir_all(parent).unwrap();
-    }
+    let mut asset_paths = Vec::new();
+    world.add_dependencies_mut(&mut asset_paths);
+
+    process_asset_paths(assets, asset_paths, &true_world_path);
+
+    let bytes = pd_asset::rkyv::to_bytes::<pd_asset::RkyvError>(&world).unwrap();
+
+    let bytes = encode_archive(AssetKind::World, &bytes);
+
+    let export_path = assets.export_path(path, path.with_extension("wdb"));
     std::fs::write(export_path, &bytes).unwrap();
 }
 
+/// Exports the animations of an Aseprite file as [`Gif`] archives playing its sprite sheet.
+///
+/// The sheet itself is saved by [`aseprite::sync_sheets`] before any asset is converted, and
+/// exported like any other image.
+fn process_aseprite(path: &Path, assets: &mut Assets) {
+    let true_path = paths().assets.join(path);
+    let aseprite = aseprite::Aseprite::load(&true_path).unwrap();
+
+    let (table, sheet) = aseprite.names(path);
+    let sheet = path.with_file_name(sheet);
+    assets.add_dependency(&true_path, &sheet);
+    assets.add_asset(sheet, true);
+
+    let image_path = path::to_playdate(&path.with_file_name(table));
+    for (name, frames) in aseprite.animations(path) {
+        let seconds = frames.iter().map(|frame| frame.duration).sum::<u32>() as f32 / 1000.0;
+        let gif = Gif {
+            image_path: image_path.clone(),
+            fps: frames.len() as f32 / seconds.max(f32::EPSILON),
+            frames,
+        };
+        let bytes = pd_asset::rkyv::to_bytes::<pd_asset::RkyvError>(&gif).unwrap();
+        let bytes = encode_archive(AssetKind::Gif, &bytes);
+
+        let export_path = assets.export_path(path, path.with_file_name(format!("{name}.gifb")));
+        std::fs::write(export_path, &bytes).unwrap();
+    }
+}
+
+/// Copies a Playdate font, and queues the `<name>-table-W-H.png` image table its glyphs are in
+/// (unless they're inside the `.fnt`), since the compiler needs it next to the font.
+fn process_font(path: &Path, assets: &mut Assets) {
+    process_default(path, assets);
+
+    let true_path = paths().assets.join(path);
+    let stem = path.file_stem().unwrap().to_string_lossy();
+    let table_regex =
+        Regex::new(&format!(r#"^{}-table-\d+-\d+\.png$"#, regex::escape(&stem))).unwrap();
+    for file in fs::read_dir(true_path.parent().unwrap()).unwrap() {
+        let file_name = file.unwrap().file_name();
+        if table_regex.is_match(&file_name.to_string_lossy()) {
+            let table = path.with_file_name(file_name);
+            assets.add_dependency(&true_path, &table);
+            assets.add_asset(table, true);
+        }
+    }
+}
+
+/// The object templates (`.tx`) the map at `map_path` (including the `assets` folder) uses,
+/// relative to the `assets` folder.
+fn template_paths(map_path: &Path) -> Vec<PathBuf> {
+    static TEMPLATE_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"template="(?<path>[^"]+)""#).unwrap());
+
+    let Ok(map) = fs::read_to_string(map_path) else {
+        return Vec::new();
+    };
+    let templates: IndexSet<PathBuf> = TEMPLATE_REGEX
+        .captures_iter(&map)
+        .map(|captures| path::resolve(map_path, &captures["path"]))
+        .collect();
+    templates.into_iter().collect()
+}
+
+/// Packs the exported archives of every bundle in the `[bundles]` table of the manifest into a
+/// single file, so the game can load them with one file open. Each bundle lists assets like the
+/// `assets` array; everything they reference is packed too.
+///
+/// A bundle named after a map (e.g. `"level-1" = ["level-1.tmx"]`) is mounted by the game when
+/// that map loads. Images and fonts are only ever loaded by path on the Playdate, so they are
+/// left out.
+fn write_bundles(manifest: &toml_edit::DocumentMut, assets: &mut Assets) {
+    let Some(bundles) = manifest.get("bundles").and_then(Item::as_table) else {
+        return;
+    };
+
+    for (name, members) in bundles.iter() {
+        println!("writing bundle: {}", name);
+
+        let roots = members
+            .as_array()
+            .expect("bundle members must be an array of asset paths")
+            .iter()
+            .map(|member| path::pd_to_pc(member.as_str().unwrap().to_string()));
+
+        let mut index = BundleIndex::default();
+        let mut payloads = Vec::new();
+        for member in assets.with_dependencies(roots) {
+            let export = path::pc_to_pd(member);
+            let extension = Path::new(&export).extension().and_then(OsStr::to_str);
+            let compression = match extension {
+                Some("tmb" | "tsb" | "wdb" | "gifb") => Compression::None,
+                Some("png" | "pdi" | "pdt" | "fnt" | "pft") => continue,
+                // only its animations and sheet are exported
+                Some("aseprite") => continue,
+                _ => Compression::Lz4,
+            };
+
+            let bytes = fs::read(assets.export.join(&export)).unwrap();
+            let uncompressed_len = bytes.len() as u32;
+            let bytes = match compression {
+                Compression::None => bytes,
+                Compression::Lz4 => lz4_flex::compress(&bytes),
+            };
+
+            let entry = BundleEntry {
+                offset: payloads.len() as u32,
+                len: bytes.len() as u32,
+                compression,
+                uncompressed_len,
+            };
+            payloads.extend_from_slice(&bytes);
+            index.entries.insert(format!("{}/{}", ASSET_PATH, export), entry);
+        }
+
+        let index = pd_asset::rkyv::to_bytes::<pd_asset::RkyvError>(&index).unwrap();
+        let mut out = BundleHeader::new(index.len() as u32).to_bytes().to_vec();
+        out.extend_from_slice(&index);
+        out.extend_from_slice(&payloads);
+
+        let bundle = Path::new(name).with_extension(BUNDLE_EXTENSION);
+        let export_path = assets.export_path(&bundle, bundle.clone());
+        std::fs::write(export_path, &out).unwrap();
+    }
+}
+
+/// Compresses the archive bytes in [blocks](pd_asset::block) and prepends an [`AssetHeader`] so
+/// the game can reject archives built against a different schema.
+fn encode_archive(kind: AssetKind, archive: &[u8]) -> Vec<u8> {
+    let header = AssetHeader::new(kind, archive);
+    let mut out = header.to_bytes().to_vec();
+    for chunk in archive.chunks(BLOCK_SIZE) {
+        let compressed = lz4_flex::block::compress(chunk);
+        if compressed.len() < chunk.len() {
+            out.extend_from_slice(&block_prefix(compressed.len(), false));
+            out.extend_from_slice(&compressed);
+        } else {
+            out.extend_from_slice(&block_prefix(chunk.len(), true));
+            out.extend_from_slice(chunk);
+        }
+    }
+    out
+}
+
 fn process_asset_paths(assets: &mut Assets, asset_paths: Vec<&mut String>, origin: &Path) {
     for asset in asset_paths {
         const EXTENSIONS: &[[&str; 3]] = &[
             ["tmx", "tmb", "tmb"],
             ["tsx", "tsb", "tsb"],
+            ["world", "wdb", "wdb"],
             ["png", "png", "pdi"],
+            ["fnt", "fnt", "pft"],
         ];
 
         static IMAGE_TABLE_REGEX: LazyLock<Regex> =
-            LazyLock::new(|| Regex::new(r#"(?<name>.*)-table-\d+(?:-\d+)?\..+"#).unwrap());
+            LazyLock::new(|| Regex::new(r#"^(?<name>.*)-table-\d+(?:-\d+)?\..+$"#).unwrap());
 
-        if let Some(captures) = IMAGE_TABLE_REGEX.captures(asset) {
-            // pc stuff
-            {
-                let path = Path::new(asset.trim_start_matches("assets\\"));
-                let mut path = origin.parent().unwrap().join(path);
-                path = path.strip_prefix("assets\\").unwrap().to_path_buf();
+        // relative to the assets folder
+        let mut path = path::resolve(origin, asset);
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
 
-                assets.add_asset(path, true);
-            }
-            // playdate stuff
-            {
-                // start with "tiles-16-16.png"
-                // now "tiles"
-                let name = &captures["name"];
-                // now "tiles" (not sure why this is here, but we ball)
-                let path = Path::new(name.trim_start_matches("assets\\"));
-                // now "parent\tiles"
-                let path = origin.parent().unwrap().join(path);
-                // now "parent/tiles"
-                *asset = path.to_string_lossy().to_string().replace("\\", "/");
+        if let Some(captures) = IMAGE_TABLE_REGEX.captures(&file_name) {
+            // the playdate loads image tables without the "-table-W-H.png" suffix,
+            // so "tiles-table-16-16.png" is "tiles" on the playdate
+            *asset = path::to_playdate(&path.with_file_name(&captures["name"]));
+
+            // export the animations of the Aseprite file the sheet is saved from too
+            if let Some(source) = aseprite::source_of(&path) {
+                assets.add_dependency(origin, &source);
+                assets.add_asset(source, true);
             }
-            // correct playdate file name, now let's add it to assets
         } else {
-            let path = Path::new(asset.trim_start_matches("assets\\"));
-            let mut path = origin.parent().unwrap().join(path);
-
             let extension = EXTENSIONS
                 .iter()
                 .find(|[x, _, _]| asset.ends_with(x))
@@ -274,22 +895,87 @@ fn process_asset_paths(assets: &mut Assets, asset_paths: Vec<&mut String>, origi
             let [pc, _export, pd] = extension;
 
             path.set_extension(pc);
-
-            let mut game = path.clone();
-            game.set_extension(pd);
-            let game = game.to_string_lossy().to_string().replace("\\", "/");
-            *asset = game;
-
-            path = path.strip_prefix("assets\\").unwrap().to_path_buf();
-
-            assets.add_asset(path, true);
+            *asset = path::to_playdate(&path.with_extension(pd));
         }
+
+        assets.add_dependency(origin, &path);
+        assets.add_asset(path, true);
     }
 }
 
 pub mod path {
+    use crate::{ASSET_PATH, paths};
     use std::ffi::OsStr;
-    use std::path::PathBuf;
+    use std::path::{Component, Path, PathBuf};
+
+    /// Removes `.` and resolves `..` in `path` without touching the file system.
+    pub fn normalize(path: &Path) -> PathBuf {
+        let mut out = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if matches!(out.components().next_back(), Some(Component::Normal(_))) {
+                        out.pop();
+                    } else {
+                        out.push("..");
+                    }
+                }
+                other => out.push(other),
+            }
+        }
+        out
+    }
+
+    /// `path` with `/` between its components, whatever the platform.
+    pub fn to_slash(path: &Path) -> String {
+        let mut out = String::new();
+        for component in path.components() {
+            match component {
+                Component::Prefix(prefix) => out.push_str(&prefix.as_os_str().to_string_lossy()),
+                Component::RootDir => out.push('/'),
+                other => {
+                    if !out.is_empty() && !out.ends_with('/') {
+                        out.push('/');
+                    }
+                    out.push_str(&other.as_os_str().to_string_lossy());
+                }
+            }
+        }
+        out
+    }
+
+    /// The path on the Playdate of the asset at `path`, relative to the assets folder.
+    pub fn to_playdate(path: &Path) -> String {
+        format!("{ASSET_PATH}/{}", to_slash(path))
+    }
+
+    /// Resolves `asset`, referenced by the file at `origin` (which includes the assets folder),
+    /// to a path relative to the assets folder.
+    ///
+    /// `asset` is either already joined with the assets folder, like the paths Tiled resolves,
+    /// or relative to the folder of `origin`.
+    pub fn resolve(origin: &Path, asset: &str) -> PathBuf {
+        let assets = &paths().assets;
+        let asset = normalize(Path::new(asset));
+        let asset = if asset.starts_with(assets) {
+            asset
+        } else {
+            normalize(&origin.parent().unwrap().join(asset))
+        };
+
+        asset
+            .strip_prefix(assets)
+            .unwrap_or_else(|_| {
+                panic!(
+                    "{}, referenced by {}, is outside of {}",
+                    asset.display(),
+                    origin.display(),
+                    assets.display()
+                )
+            })
+            .to_path_buf()
+    }
 
     pub fn pc_to_pd(mut path_pc: PathBuf) -> String {
         match path_pc.extension() {
@@ -300,16 +986,16 @@ pub mod path {
             Some(x) if x == OsStr::new("tsx") => {
                 path_pc.set_extension("tsb");
             }
+            Some(x) if x == OsStr::new("world") => {
+                path_pc.set_extension("wdb");
+            }
             Some(x) if x == OsStr::new("png") => {
                 path_pc.set_extension("pdi");
             }
             Some(_) => {}
         }
 
-        let mut path = path_pc.to_string_lossy().to_string();
-        path = path.replace("\\", "/");
-
-        path
+        to_slash(&path_pc)
     }
 
     pub fn pd_to_pc(path: String) -> PathBuf {
@@ -322,6 +1008,9 @@ pub mod path {
             Some(x) if x == OsStr::new("tsb") => {
                 path.set_extension("tsx");
             }
+            Some(x) if x == OsStr::new("wdb") => {
+                path.set_extension("world");
+            }
             Some(x) if x == OsStr::new("pdi") => {
                 path.set_extension("png");
             }
@@ -333,20 +1022,34 @@ pub mod path {
 }
 
 /// Copies file to export folder. Path must be relative to `assets` folder.
-/// I.e. `"tiles.png"` corresponds to `"./assets\\tiles.png"`
-pub fn process_default(path: &Path) {
-    let old_path = Path::new(ASSET_PATH).join(path);
-    let new_path = Path::new(ASSET_PATH).join(EXPORT_FOLDER).join(path);
+/// I.e. `"tiles.png"` corresponds to `"assets/tiles.png"`
+///
+/// PNGs are quantized to black, white and transparent with the [`Dither`](dither::Dither) the
+/// manifest gives them, and a preview of how they look on the Playdate screen is written to the
+/// preview folder.
+pub fn process_default(path: &Path, assets: &mut Assets) {
+    let old_path = paths().assets.join(path);
+    let new_path = assets.export_path(path, path.to_path_buf());
     // dbg!(&old_path, &new_path);
 
-    std::fs::create_dir_all(new_path.parent().unwrap()).unwrap();
-    std::fs::copy(old_path, new_path).unwrap();
+    if path.extension() != Some(OsStr::new("png")) {
+        std::fs::copy(old_path, new_path).unwrap();
+        return;
+    }
+
+    let mut image = image::open(&old_path).unwrap().to_rgba8();
+    assets.dither.for_image(path).apply(&mut image);
+    image.save(new_path).unwrap();
+
+    let preview_path = preview_dir().join(path);
+    std::fs::create_dir_all(preview_path.parent().unwrap()).unwrap();
+    dither::preview(&image).save(preview_path).unwrap();
 
     // path.parent().unwrap()
     // std::fs::create_dir_all(path.parent())
 }
 
-fn generate_transition() -> Gif {
+fn generate_transition(assets: &mut Assets) -> Gif {
     let fps = 50.0;
     let length = 0.4;
     
@@ -362,7 +1065,7 @@ fn generate_transition() -> Gif {
     
     // let mut frames = Vec::new();
     let mut full_image = RgbaImage::new(n as u32 * 400, 240);
-    let path = "screen-transition-ease-out-table-400-240.png";
+    let path = Path::new("screen-transition-ease-out-table-400-240.png");
     
     for i in 0..n {
         let t = (i as f32) / ((n - 1) as f32);
@@ -379,10 +1082,28 @@ fn generate_transition() -> Gif {
         full_image.copy_from(&image, i as u32 * 400, 0).unwrap();
     }
     
-    full_image.save(format!("assets/export/{}", path)).unwrap();
+    let export_path = assets.export_path(path, path.to_path_buf());
+    full_image.save(export_path).unwrap();
+    assets.add_asset(path.to_path_buf(), false);
 
     Gif {
-        image_path: path.to_string(),
+        // the playdate loads image tables without the "-table-W-H.png" suffix
+        image_path: format!("{ASSET_PATH}/screen-transition-ease-out"),
         fps,
+        frames: Vec::new(),
     }
 }
+
+/// Renders the screen transition and exports it as a [`Gif`] archive next to its image table.
+fn process_transition(assets: &mut Assets) {
+    println!("processing screen transition");
+
+    let gif = generate_transition(assets);
+    let bytes = pd_asset::rkyv::to_bytes::<pd_asset::RkyvError>(&gif).unwrap();
+    let bytes = encode_archive(AssetKind::Gif, &bytes);
+
+    let path = Path::new("screen-transition.gifb");
+    let export_path = assets.export_path(path, path.to_path_buf());
+    std::fs::write(export_path, &bytes).unwrap();
+    assets.add_asset(path.to_path_buf(), false);
+}
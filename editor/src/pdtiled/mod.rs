@@ -1,22 +1,31 @@
-use crate::ASSET_PATH;
+pub mod reverse;
+
+use crate::dither::{BAYER_8X8, Dither};
 use geo::{BooleanOps, Coord, LineString, MultiPolygon, Polygon};
 use image::{GenericImageView, RgbaImage};
+use std::collections::HashSet;
 use std::mem;
 use std::ops::Deref;
-use std::path::PathBuf;
 use tiled::{
-    FiniteTileLayer, Layer, LayerTile, LayerTileData, LayerType, Object, PropertyValue, TileLayer,
+    Chunk, FiniteTileLayer, Layer, LayerTile, LayerTileData, LayerType, Object, PropertyValue, TileLayer,
     TilesetLocation,
 };
 use pd_asset::properties::PropertyValue as PVPD;
 use pd_asset::tilemap::{
-    ImageLayer, Layer as LayerPD, LayerData, ObjectData, ObjectLayer, ObjectShape, Tile,
+    GroupLayer, ImageLayer, Layer as LayerPD, LayerData, ObjectData, ObjectLayer, ObjectShape,
+    Orientation, TileEncoding, TileOverflow, TileStorage, WideTile,
+};
+use pd_asset::tilemap::{
+    ChunkData, HorizontalAlignment, LayerCollision, TextData, Tilemap, VerticalAlignment,
 };
-use pd_asset::tilemap::{LayerCollision, Tilemap};
-use pd_asset::tileset::{TileData, Tileset};
+use pd_asset::tileset::{Frame, TileData, Tileset};
+use pd_asset::world::{World, WorldMap};
 
 pub fn convert_map(map: tiled::Map) -> Tilemap {
-    let layers = map.layers().map(|layer| convert_layer(layer)).collect();
+    let layers = map
+        .layers()
+        .map(|layer| convert_layer(layer, Fade::default()))
+        .collect();
 
     let tilesets = map
         .tilesets()
@@ -30,58 +39,177 @@ pub fn convert_map(map: tiled::Map) -> Tilemap {
         })
         .collect();
 
+    let infinite = map.infinite();
+    let orientation = match map.orientation {
+        tiled::Orientation::Orthogonal => Orientation::Orthogonal,
+        tiled::Orientation::Isometric => Orientation::Isometric,
+        tiled::Orientation::Staggered => Orientation::Staggered,
+        tiled::Orientation::Hexagonal => Orientation::Hexagonal,
+    };
+    if orientation != Orientation::Orthogonal {
+        println!("{:?}: only orthogonal maps are drawn correctly", map.source);
+    }
+    let background_color = map.background_color.map(|c| pd_asset::properties::Color {
+        red: c.red,
+        green: c.green,
+        blue: c.blue,
+        alpha: c.alpha,
+    });
     let properties = convert_properties(map.properties);
 
-    Tilemap {
+    let mut tilemap = Tilemap {
         tilesets,
         layers,
         properties,
         tile_width: map.tile_width,
         tile_height: map.tile_height,
+        width: map.width,
+        height: map.height,
+        infinite,
+        orientation,
+        background_color,
+        tile_encoding: TileEncoding::Wide,
+    };
+
+    // layers are converted with the wide encoding, shrink them if every tile fits
+    if let Err(tile) = tilemap.set_tile_encoding(TileEncoding::Compact) {
+        println!(
+            "{:?}: {tile} doesn't fit the compact tile encoding (256 tiles per tileset, 16 tilesets), \
+            using the wide encoding",
+            map.source
+        );
     }
+
+    tilemap
 }
 
-pub fn convert_layer(layer: Layer) -> LayerPD {
+/// Converts a layer, baking its images with its opacity and tint combined with `parent`, the
+/// fade of the groups it's in.
+pub fn convert_layer(layer: Layer, parent: Fade) -> LayerPD {
     let data = layer.deref().clone();
-    let layer_data = convert_layer_data(layer);
+    let layer_data = convert_layer_data(layer, parent.then(&data));
 
     LayerPD {
         name: layer.name.clone(),
         id: layer.id(),
         x: data.offset_x,
         y: data.offset_y,
+        parallax_x: data.parallax_x,
+        parallax_y: data.parallax_y,
+        opacity: data.opacity,
+        tint_color: data.tint_color.map(|c| pd_asset::properties::Color {
+            red: c.red,
+            green: c.green,
+            blue: c.blue,
+            alpha: c.alpha,
+        }),
         visible: data.visible,
         layer_data,
         properties: convert_properties(data.properties),
     }
 }
 
-pub fn convert_layer_data(main_layer: Layer) -> LayerData {
+/// Opacity and tint of a layer combined with those of the groups it's in, the same as in Tiled.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Fade {
+    pub opacity: f32,
+    /// RGBA multiplier, each in `0..=1`.
+    pub tint: [f32; 4],
+}
+
+impl Default for Fade {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0,
+            tint: [1.0; 4],
+        }
+    }
+}
+
+impl Fade {
+    /// The fade of a layer with this as the fade of its parent.
+    pub fn then(self, layer: &tiled::LayerData) -> Self {
+        let mut tint = self.tint;
+        if let Some(color) = layer.tint_color {
+            let color = [color.red, color.green, color.blue, color.alpha];
+            for (tint, color) in tint.iter_mut().zip(color) {
+                *tint *= color as f32 / 255.0;
+            }
+        }
+
+        Self {
+            opacity: self.opacity * layer.opacity,
+            tint,
+        }
+    }
+
+    /// Tints `image`, then dithers its alpha so it looks as opaque as it should on the 1-bit
+    /// screen, where pixels are either drawn or not.
+    pub fn apply(&self, image: &mut RgbaImage) {
+        if *self == Self::default() {
+            return;
+        }
+
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            for (channel, tint) in pixel.0[..3].iter_mut().zip(self.tint) {
+                *channel = (*channel as f32 * tint).round() as u8;
+            }
+
+            let alpha = pixel.0[3] as f32 / 255.0 * self.tint[3] * self.opacity;
+            let threshold = BAYER_8X8[(y % 8) as usize][(x % 8) as usize];
+            pixel.0[3] = if alpha * 64.0 > threshold as f32 { 255 } else { 0 };
+        }
+    }
+}
+
+pub fn convert_layer_data(main_layer: Layer, fade: Fade) -> LayerData {
     match main_layer.layer_type() {
         LayerType::Image(layer) => {
             let Some(image) = layer.image.clone() else {
                 panic!("image not set on layer");
             };
 
-            // let
+            // the image may be shared with other layers, so fade a copy of it
+            let source = if fade == Fade::default() {
+                image.source.to_string_lossy().to_string()
+            } else {
+                let mut faded = image::open(&image.source)
+                    .unwrap_or_else(|err| panic!("{}: {err}", image.source.display()))
+                    .to_rgba8();
+                fade.apply(&mut faded);
+                let mut name = main_layer.map().source.file_stem().unwrap().to_owned();
+                name.push(format!("-layer-({}).png", main_layer.id()));
+                // next to the map, as the name is relative to it
+                let output_path = main_layer.map().source.with_file_name(&name);
+                faded.save(&output_path).unwrap();
+                name.to_string_lossy().to_string()
+            };
 
             LayerData::ImageLayer(ImageLayer {
-                source: image.source.to_string_lossy().to_string(),
+                source,
                 width: image.width,
                 height: image.height,
+                repeat_x: layer.repeat_x,
+                repeat_y: layer.repeat_y,
             })
         }
-        LayerType::Group(_) => todo!("group layer"),
+        LayerType::Group(group) => {
+            let layers = group
+                .layers()
+                .map(|layer| convert_layer(layer, fade))
+                .collect();
+
+            LayerData::GroupLayer(GroupLayer { layers })
+        }
         LayerType::Tiles(tiles) => {
             match tiles {
                 TileLayer::Finite(layer) => {
                     let mut tiles = Vec::with_capacity((layer.width() * layer.height()) as usize);
                     for y in 0..layer.height() {
                         for x in 0..layer.width() {
-                            let mut tile = Tile::NONE;
-                            if let Some(t) = layer.get_tile_data(x as i32, y as i32) {
-                                tile = Some(convert_tile(*t));
-                            }
+                            let tile = layer
+                                .get_tile_data(x as i32, y as i32)
+                                .map(|t| convert_layer_tile(&main_layer, *t));
                             tiles.push(tile);
                         }
                     }
@@ -90,33 +218,46 @@ pub fn convert_layer_data(main_layer: Layer) -> LayerData {
                         .any(|(_, i)| is_generate_collision(dbg!(i)))
                         .then(|| generate_layer_collision(&layer));
 
-                    let image = render_tile_layer(layer);
+                    let mut image = render_tile_layer(layer);
+                    fade.apply(&mut image);
+                    if let Some(dither) = layer_dither(&main_layer) {
+                        dither.apply(&mut image);
+                    }
                     // image.save()
-                    let mut output_path = PathBuf::from(ASSET_PATH);
-                    // layer.map().source.rel
                     let mut name = layer.map().source.file_stem().unwrap().to_owned();
                     name.push("-layer-(");
                     name.push(main_layer.id().to_string());
                     name.push(").png");
-                    output_path.push(name.clone());
+                    // next to the map, as the name is relative to it
+                    let output_path = layer.map().source.with_file_name(&name);
                     std::fs::create_dir_all(output_path.parent().unwrap()).unwrap();
                     image.save(&output_path).unwrap();
 
                     LayerData::FiniteTileLayer(pd_asset::tilemap::FiniteTileLayer {
                         width: layer.width(),
                         height: layer.height(),
-                        tiles,
+                        tiles: TileStorage::Wide(tiles),
                         layer_collision,
                         image: Some(name.to_string_lossy().to_string()),
                     })
                 }
-                TileLayer::Infinite(tiles) => { 
-                    for (pos, chunk) in tiles.chunks() {
-                        dbg!(pos);
-                    }
-                    
-                    todo!("infinite tile layer")
-                },
+                TileLayer::Infinite(layer) => {
+                    let generate_collision = main_layer
+                        .properties
+                        .iter()
+                        .any(|(_, i)| is_generate_collision(i));
+
+                    let chunks = layer
+                        .chunks()
+                        .map(|(pos, chunk)| {
+                            let chunk =
+                                convert_chunk(&main_layer, pos, chunk, generate_collision, fade);
+                            (pos, chunk)
+                        })
+                        .collect();
+
+                    LayerData::InfiniteTileLayer(pd_asset::tilemap::InfiniteTileLayer { chunks })
+                }
             }
         }
         LayerType::Objects(layer) => {
@@ -127,55 +268,148 @@ pub fn convert_layer_data(main_layer: Layer) -> LayerData {
     }
 }
 
+fn convert_chunk(
+    main_layer: &Layer,
+    (chunk_x, chunk_y): (i32, i32),
+    chunk: Chunk,
+    generate_collision: bool,
+    fade: Fade,
+) -> ChunkData {
+    let (width, height) = (ChunkData::WIDTH, ChunkData::HEIGHT);
+
+    let mut tiles = vec![None; ChunkData::TILE_COUNT];
+    for y in 0..height {
+        for x in 0..width {
+            if let Some(t) = chunk.get_tile_data(x as i32, y as i32) {
+                tiles[(x + y * width) as usize] = Some(convert_layer_tile(main_layer, *t));
+            }
+        }
+    }
+
+    let collision = generate_collision
+        .then(|| generate_collision_lines(chunk.map(), width, height, |x, y| chunk.get_tile(x, y)))
+        .filter(|collision| !collision.lines.is_empty());
+
+    let image = if tiles.iter().any(Option::is_some) {
+        let mut image = render_tiles(chunk.map(), width, height, |x, y| chunk.get_tile(x, y));
+        fade.apply(&mut image);
+        if let Some(dither) = layer_dither(main_layer) {
+            dither.apply(&mut image);
+        }
+        let mut name = chunk.map().source.file_stem().unwrap().to_owned();
+        name.push(format!(
+            "-layer-({})-chunk-({chunk_x}_{chunk_y}).png",
+            main_layer.id()
+        ));
+        let output_path = chunk.map().source.with_file_name(&name);
+        std::fs::create_dir_all(output_path.parent().unwrap()).unwrap();
+        image.save(&output_path).unwrap();
+
+        Some(name.to_string_lossy().to_string())
+    } else {
+        None
+    };
+
+    ChunkData {
+        tiles: TileStorage::Wide(tiles),
+        collision,
+        image,
+    }
+}
+
+/// The `dither` string property of a tile layer, which quantizes its baked image instead of the
+/// dither the manifest gives images.
+fn layer_dither(layer: &Layer) -> Option<Dither> {
+    match layer.properties.get("dither")? {
+        PropertyValue::StringValue(dither) => Some(
+            dither
+                .parse()
+                .unwrap_or_else(|err| panic!("dither of layer {:?}: {err}", layer.name)),
+        ),
+        _ => None,
+    }
+}
+
 fn generate_layer_collision(layer: &FiniteTileLayer) -> LayerCollision {
-    let mut multi_polygon = MultiPolygon::new(Vec::new());
-    let tile_width = layer.map().tile_width as f32;
-    let tile_height = layer.map().tile_height as f32;
+    generate_collision_lines(layer.map(), layer.width(), layer.height(), |x, y| {
+        layer.get_tile(x, y)
+    })
+}
 
-    for y in 0..layer.height() as i32 {
-        for x in 0..layer.width() as i32 {
-            if let Some(tile) = layer.get_tile(x, y) {
+/// Merges the collision of every tile in a `width` x `height` area into polylines.
+fn generate_collision_lines<'map>(
+    map: &tiled::Map,
+    width: u32,
+    height: u32,
+    get_tile: impl Fn(i32, i32) -> Option<LayerTile<'map>>,
+) -> LayerCollision {
+    let mut multi_polygon = MultiPolygon::new(Vec::new());
+    let tile_width = map.tile_width as f32;
+    let tile_height = map.tile_height as f32;
+    // tileset, tile and object of every bad shape already reported
+    let mut reported = HashSet::new();
+
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            if let Some(tile) = get_tile(x, y) {
                 let tile_data = tile.get_tile().unwrap();
-                let object_data = tile_data
-                    .collision
-                    .as_ref()
-                    .map(|s| s.object_data())
-                    .unwrap_or_default();
-                if object_data.is_empty() {
+                let Some(collision) = &tile_data.collision else {
                     continue;
-                }
-                // must have none or only 1
-                assert_eq!(object_data.len(), 1);
-                let mut points = match &object_data[0].shape {
-                    tiled::ObjectShape::Polygon { points } => points.clone(),
-                    x => panic!("only polygon data supported: {:?}", x),
+                };
+                let tileset = tile.get_tileset();
+                let segments = match tileset.properties.get("ellipse-segments") {
+                    Some(PropertyValue::IntValue(segments)) => *segments,
+                    _ => ELLIPSE_SEGMENTS,
                 };
 
-                // flip coords
+                for object in collision.object_data() {
+                    let points = collision_points(object, segments).and_then(|points| {
+                        if tile.flip_d && tile_width != tile_height {
+                            return Err("tiles flipped diagonally must be square".to_string());
+                        }
+                        Ok(points)
+                    });
+                    let mut points = match points {
+                        Ok(points) => points,
+                        Err(reason) => {
+                            if reported.insert((tileset.name.clone(), tile.id(), object.id())) {
+                                eprintln!(
+                                    "warning: tileset {:?}, tile {}: skipped collision shape {}: \
+                                     {reason}",
+                                    tileset.name,
+                                    tile.id(),
+                                    object.id()
+                                );
+                            }
+                            continue;
+                        }
+                    };
 
-                if tile.flip_d {
-                    assert_eq!(tile_width, tile_height);
-                    points.iter_mut().for_each(|(x, y)| mem::swap(x, y));
-                }
-                if tile.flip_h {
-                    points.iter_mut().for_each(|(x, _)| *x = tile_width - *x);
-                }
-                if tile.flip_v {
-                    points.iter_mut().for_each(|(_, y)| *y = tile_height - *y);
-                }
-                // offset by tile position
-                points.iter_mut().for_each(|(x_p, y_p)| {
-                    *x_p += x as f32 * tile_width;
-                    *y_p += y as f32 * tile_height;
-                });
-
-                // merge with multi
-                let polygon = Polygon::new(
-                    LineString(points.into_iter().map(Coord::from).collect()),
-                    vec![],
-                );
+                    // flip coords
 
-                multi_polygon = multi_polygon.union(&MultiPolygon(vec![polygon]));
+                    if tile.flip_d {
+                        points.iter_mut().for_each(|(x, y)| mem::swap(x, y));
+                    }
+                    if tile.flip_h {
+                        points.iter_mut().for_each(|(x, _)| *x = tile_width - *x);
+                    }
+                    if tile.flip_v {
+                        points.iter_mut().for_each(|(_, y)| *y = tile_height - *y);
+                    }
+                    // offset by tile position
+                    points.iter_mut().for_each(|(x_p, y_p)| {
+                        *x_p += x as f32 * tile_width;
+                        *y_p += y as f32 * tile_height;
+                    });
+
+                    // merge with multi
+                    let polygon = Polygon::new(
+                        LineString(points.into_iter().map(Coord::from).collect()),
+                        vec![],
+                    );
+
+                    multi_polygon = multi_polygon.union(&MultiPolygon(vec![polygon]));
+                }
             }
         }
     }
@@ -203,20 +437,89 @@ fn generate_layer_collision(layer: &FiniteTileLayer) -> LayerCollision {
     LayerCollision { lines }
 }
 
+/// Segments ellipses are approximated with, unless the tileset has an `ellipse-segments` or the
+/// ellipse a `segments` int property.
+const ELLIPSE_SEGMENTS: i32 = 16;
+
+/// The outline of a collision shape of a tile, in pixels from the top-left of the tile, or why
+/// it can't collide. Polylines are closed, as only areas collide.
+fn collision_points(object: &tiled::ObjectData, segments: i32) -> Result<Vec<(f32, f32)>, String> {
+    let mut points = match &object.shape {
+        tiled::ObjectShape::Rect { width, height } => {
+            vec![(0.0, 0.0), (*width, 0.0), (*width, *height), (0.0, *height)]
+        }
+        tiled::ObjectShape::Ellipse { width, height } => {
+            let segments = match object.properties.get("segments") {
+                Some(PropertyValue::IntValue(segments)) => *segments,
+                _ => segments,
+            };
+            if segments < 3 {
+                return Err(format!("ellipses need at least 3 segments, not {segments}"));
+            }
+
+            let (rx, ry) = (width / 2.0, height / 2.0);
+            (0..segments)
+                .map(|i| {
+                    let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+                    let (sin, cos) = angle.sin_cos();
+                    (rx + rx * cos, ry + ry * sin)
+                })
+                .collect()
+        }
+        tiled::ObjectShape::Polygon { points } | tiled::ObjectShape::Polyline { points } => {
+            points.clone()
+        }
+        _ => return Err("only rectangles, ellipses, polygons and polylines collide".to_string()),
+    };
+
+    let area: f32 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|((x0, y0), (x1, y1))| x0 * y1 - x1 * y0)
+        .sum();
+    if points.len() < 3 || area.abs() < f32::EPSILON {
+        return Err("the shape has no area".to_string());
+    }
+
+    // rotate around the position of the object, then move it there
+    let (sin, cos) = object.rotation.to_radians().sin_cos();
+    for (x, y) in points.iter_mut() {
+        (*x, *y) = (
+            object.x + *x * cos - *y * sin,
+            object.y + *x * sin + *y * cos,
+        );
+    }
+    Ok(points)
+}
+
 pub fn render_tile_layer(layer: FiniteTileLayer) -> RgbaImage {
-    let width = layer.map().tile_width * layer.width();
-    let height = layer.map().tile_height * layer.height();
-    let mut image = RgbaImage::new(width, height);
+    render_tiles(layer.map(), layer.width(), layer.height(), |x, y| {
+        layer.get_tile(x, y)
+    })
+}
 
-    for y in 0..layer.height() {
-        for x in 0..layer.width() {
-            if let Some(tile) = layer.get_tile(x as i32, y as i32) {
+/// Renders a `width` x `height` area of tiles into a single image.
+pub fn render_tiles<'map>(
+    map: &tiled::Map,
+    width: u32,
+    height: u32,
+    get_tile: impl Fn(i32, i32) -> Option<LayerTile<'map>>,
+) -> RgbaImage {
+    let mut image = RgbaImage::new(map.tile_width * width, map.tile_height * height);
+
+    for y in 0..height {
+        for x in 0..width {
+            if let Some(tile) = get_tile(x as i32, y as i32) {
+                // animated tiles are drawn as separate sprites on top of the baked image
+                if is_animated(&tile) {
+                    continue;
+                }
                 let tile_image = render_layer_tile(tile);
                 image::imageops::overlay(
                     &mut image,
                     &tile_image,
-                    (x * layer.map().tile_width) as i64,
-                    (y * layer.map().tile_height) as i64,
+                    (x * map.tile_width) as i64,
+                    (y * map.tile_height) as i64,
                 );
             }
         }
@@ -225,6 +528,11 @@ pub fn render_tile_layer(layer: FiniteTileLayer) -> RgbaImage {
     image
 }
 
+fn is_animated(tile: &LayerTile) -> bool {
+    tile.get_tile()
+        .is_some_and(|tile| tile.animation.is_some())
+}
+
 pub fn render_layer_tile(tile: LayerTile) -> RgbaImage {
     // let image = tile.get_tileset().image.as_ref().unwrap();
 
@@ -290,29 +598,55 @@ pub fn flip_diagonal(mut image: RgbaImage) -> RgbaImage {
     image
 }
 
-pub fn convert_tile(tile: LayerTileData) -> Tile {
-    let id = u8::try_from(tile.id()).expect("convert tile id to u8");
-    let idx = u8::try_from(tile.tileset_index()).expect("convert tileset index to u8");
+pub fn convert_tile(tile: LayerTileData) -> Result<WideTile, TileOverflow> {
+    WideTile::new(
+        tile.id(),
+        tile.flip_h,
+        tile.flip_v,
+        tile.flip_d,
+        tile.tileset_index() as u32,
+    )
+}
 
-    Tile::new(id, tile.flip_h, tile.flip_v, tile.flip_d, idx)
+fn convert_layer_tile(layer: &Layer, tile: LayerTileData) -> WideTile {
+    convert_tile(tile).unwrap_or_else(|err| {
+        panic!(
+            "{:?}: layer {} ({:?}): {err}",
+            layer.map().source,
+            layer.id(),
+            layer.name
+        )
+    })
 }
 
 pub fn convert_object(object: Object) -> ObjectData {
-    // let data = object.
+    let mut properties = object.properties.clone();
     let shape = if let Some(tile) = object.tile_data() {
         let TilesetLocation::Map(idx) = tile.tileset_location() else {
             panic!("embedded tile");
         };
-        let idx = *idx;
-        assert!(idx < 16);
-
-        ObjectShape::Tile(Tile::new(
-            u8::try_from(tile.id()).expect("too many tiles in one map"),
-            tile.flip_h,
-            tile.flip_v,
-            tile.flip_d,
-            idx as u8,
-        ))
+        let tile = WideTile::new(tile.id(), tile.flip_h, tile.flip_v, tile.flip_d, *idx as u32)
+            .unwrap_or_else(|err| panic!("tile object {}: {err}", object.id()));
+        // tiled gives tile objects a rect shape with the size the tile is drawn at
+        let &tiled::ObjectShape::Rect { width, height } = &object.shape else {
+            panic!("tile object {} doesn't have a rect shape", object.id());
+        };
+
+        ObjectShape::Tile {
+            tile,
+            width,
+            height,
+        }
+    } else if let tiled::ObjectShape::Text { .. } = &object.shape {
+        let font = match properties.remove("font") {
+            Some(PropertyValue::FileValue(font)) => Some(font),
+            Some(other) => panic!(
+                "`font` property of text object {} must be a file, found {other:?}",
+                object.id()
+            ),
+            None => None,
+        };
+        convert_text(object.shape.clone(), font)
     } else {
         convert_object_shape(object.shape.clone())
     };
@@ -323,8 +657,9 @@ pub fn convert_object(object: Object) -> ObjectData {
         name: object.name.clone(),
         x: object.x,
         y: object.y,
+        rotation: object.rotation,
         visible: object.visible,
-        properties: convert_properties(object.properties.clone()),
+        properties: convert_properties(properties),
     }
 }
 
@@ -336,10 +671,52 @@ pub fn convert_object_shape(shape: tiled::ObjectShape) -> ObjectShape {
         OS::Polyline { points } => ObjectShape::Polyline { points },
         OS::Polygon { points } => ObjectShape::Polygon { points },
         OS::Point(x, y) => ObjectShape::Point(x, y),
-        OS::Text { .. } => panic!("text object is unsupported"),
+        text @ OS::Text { .. } => convert_text(text, None),
     }
 }
 
+/// Converts a text object, drawing it with the Playdate font at `font` (or the system font).
+pub fn convert_text(shape: tiled::ObjectShape, font: Option<String>) -> ObjectShape {
+    let tiled::ObjectShape::Text {
+        font_family,
+        pixel_size,
+        wrap,
+        halign,
+        valign,
+        text,
+        width,
+        height,
+        ..
+    } = shape
+    else {
+        panic!("not a text object");
+    };
+
+    let halign = match halign {
+        tiled::HorizontalAlignment::Left => HorizontalAlignment::Left,
+        tiled::HorizontalAlignment::Center => HorizontalAlignment::Center,
+        tiled::HorizontalAlignment::Right => HorizontalAlignment::Right,
+        tiled::HorizontalAlignment::Justify => HorizontalAlignment::Justify,
+    };
+    let valign = match valign {
+        tiled::VerticalAlignment::Top => VerticalAlignment::Top,
+        tiled::VerticalAlignment::Center => VerticalAlignment::Center,
+        tiled::VerticalAlignment::Bottom => VerticalAlignment::Bottom,
+    };
+
+    ObjectShape::Text(TextData {
+        text,
+        font_family,
+        font,
+        pixel_size: pixel_size as u32,
+        wrap,
+        halign,
+        valign,
+        width,
+        height,
+    })
+}
+
 pub fn convert_properties(properties: tiled::Properties) -> pd_asset::properties::Properties {
     properties
         .into_iter()
@@ -361,7 +738,12 @@ pub fn convert_property(property: PropertyValue) -> PVPD {
         PV::BoolValue(v) => PVPD::BoolValue(v),
         PV::FloatValue(v) => PVPD::FloatValue(v),
         PV::IntValue(v) => PVPD::IntValue(v),
-        PV::ColorValue(_) => panic!("color unsupported"),
+        PV::ColorValue(c) => PVPD::ColorValue(pd_asset::properties::Color {
+            red: c.red,
+            green: c.green,
+            blue: c.blue,
+            alpha: c.alpha,
+        }),
         PV::StringValue(v) => PVPD::StringValue(v),
         PV::FileValue(v) => PVPD::FileValue(v),
         PV::ObjectValue(v) => PVPD::ObjectValue(v),
@@ -383,10 +765,22 @@ pub fn convert_property(property: PropertyValue) -> PVPD {
 }
 
 pub fn convert_tileset(tileset: tiled::Tileset) -> Tileset {
-    let tiles = tileset
-        .tiles()
+    // the runtime indexes tiles by id, but `tiles()` iterates in no particular order
+    let mut tiles = tileset.tiles().collect::<Vec<_>>();
+    tiles.sort_by_key(|(id, _)| *id);
+    let tiles = tiles
+        .into_iter()
         .map(|(_i, t)| TileData {
             properties: convert_properties(t.properties.clone()),
+            animation: t.animation.as_ref().map(|frames| {
+                frames
+                    .iter()
+                    .map(|frame| Frame {
+                        tile_id: frame.tile_id,
+                        duration: frame.duration,
+                    })
+                    .collect()
+            }),
         })
         .collect();
 
@@ -395,3 +789,44 @@ pub fn convert_tileset(tileset: tiled::Tileset) -> Tileset {
         image_path: tileset.image.unwrap().source.to_string_lossy().to_string(),
     }
 }
+
+/// Converts a Tiled world. Maps without a size in the world file are loaded to measure them.
+///
+/// Pattern-based worlds aren't supported, only maps listed explicitly, so converting one panics
+/// instead of exporting an empty world.
+pub fn convert_world(world: tiled::World) -> World {
+    assert!(
+        world.patterns.as_ref().is_none_or(Vec::is_empty),
+        "{}: pattern-based worlds aren't supported, list the maps of the world explicitly",
+        world.source.display(),
+    );
+
+    let maps = world
+        .maps
+        .unwrap_or_default()
+        .into_iter()
+        .map(|map| {
+            let (width, height) = match (map.width, map.height) {
+                (Some(width), Some(height)) => (width as u32, height as u32),
+                _ => {
+                    let path = world.source.parent().unwrap().join(&map.filename);
+                    let tiled_map = tiled::Loader::new().load_tmx_map(&path).unwrap();
+                    (
+                        tiled_map.width * tiled_map.tile_width,
+                        tiled_map.height * tiled_map.tile_height,
+                    )
+                }
+            };
+
+            WorldMap {
+                path: map.filename,
+                x: map.x,
+                y: map.y,
+                width,
+                height,
+            }
+        })
+        .collect();
+
+    World { maps }
+}
@@ -0,0 +1,893 @@
+//! `editor to-tiled <file> [--out <dir>]`: rebuilds Tiled files from exported
+//! archives, so a shipped `.tmb` (or `.tsb`) can be opened in Tiled and inspected.
+//!
+//! Paths in the archives are Playdate paths (`assets/...`), they are looked up in the export
+//! folder. Referenced tilesets are rebuilt too, and images are copied next to the rebuilt files.
+//! Baked layer collision is added as a locked object layer after its tile layer.
+
+use crate::dump::load_archive;
+use crate::path::to_slash;
+use crate::{ASSET_PATH, paths};
+use anyhow::{Context, bail};
+use pd_asset::properties::{ArchivedColor, ArchivedProperties, ArchivedPropertyValue};
+use pd_asset::rkyv::primitive::ArchivedF32;
+use pd_asset::rkyv::tuple::ArchivedTuple2;
+use pd_asset::tilemap::{
+    ArchivedChunkData, ArchivedHorizontalAlignment, ArchivedLayer, ArchivedLayerCollision,
+    ArchivedLayerData, ArchivedObjectData, ArchivedObjectShape, ArchivedOrientation,
+    ArchivedTileStorage, ArchivedTilemap, ArchivedVerticalAlignment, ChunkData, WideTile,
+};
+use pd_asset::tileset::ArchivedTileset;
+use regex::Regex;
+use std::ffi::OsStr;
+use std::fmt::{Display, Write};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+
+/// Parses the arguments after `to-tiled` and rebuilds the file.
+pub fn run(args: &[String]) -> anyhow::Result<()> {
+    const USAGE: &str = "usage: editor [--export <dir>] to-tiled <file.tmb | file.tsb> [--out <dir>]";
+
+    let export_root = paths().export.clone();
+    let mut out_root = PathBuf::from("recovered");
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out_root = args.next().context(USAGE)?.into(),
+            _ if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => bail!("unexpected argument {arg:?}\n{USAGE}"),
+        }
+    }
+    let Some(path) = path else {
+        bail!("{USAGE}");
+    };
+
+    let reverse = Reverse {
+        export_root,
+        out_root,
+    };
+    // keep the layout of the export folder, so relative paths between files still work
+    let relative = path
+        .strip_prefix(&reverse.export_root)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| PathBuf::from(path.file_name().unwrap()));
+
+    let out = match path.extension().and_then(OsStr::to_str) {
+        Some("tmb") => {
+            let map = load_archive::<ArchivedTilemap>(&path)?;
+            reverse.write_map(map.access(), &relative)?
+        }
+        Some("tsb") => {
+            let tileset = load_archive::<ArchivedTileset>(&path)?;
+            reverse.write_tileset(tileset.access(), &relative, None, 0)?.0
+        }
+        _ => bail!("{}: only .tmb and .tsb archives can be converted\n{USAGE}", path.display()),
+    };
+    println!("wrote {}", out.display());
+
+    Ok(())
+}
+
+struct Reverse {
+    export_root: PathBuf,
+    out_root: PathBuf,
+}
+
+impl Reverse {
+    /// Path of a Playdate path (`assets/...`) relative to the export and output folders.
+    fn relative(pd_path: &str) -> PathBuf {
+        let path = pd_path.trim_start_matches('/');
+        let path = path
+            .strip_prefix(ASSET_PATH)
+            .map(|path| path.trim_start_matches('/'))
+            .unwrap_or(path);
+        PathBuf::from(path)
+    }
+
+    fn write(&self, relative: &Path, contents: String) -> anyhow::Result<PathBuf> {
+        let out = self.out_root.join(relative);
+        if let Some(parent) = out.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&out, contents).with_context(|| out.display().to_string())?;
+        Ok(out)
+    }
+
+    /// Copies an exported file to the same place in the output folder.
+    fn copy(&self, relative: &Path) -> anyhow::Result<()> {
+        let out = self.out_root.join(relative);
+        if let Some(parent) = out.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let from = self.export_root.join(relative);
+        std::fs::copy(&from, &out).with_context(|| from.display().to_string())?;
+        Ok(())
+    }
+
+    fn write_map(&self, map: &ArchivedTilemap, relative: &Path) -> anyhow::Result<PathBuf> {
+        let tmx = relative.with_extension("tmx");
+        let dir = tmx.parent().unwrap_or(Path::new(""));
+        let tile_size = (map.tile_width.to_native(), map.tile_height.to_native());
+
+        // tiles only store their id in the tileset, so the first gid of each tileset depends on
+        // how many tiles the ones before it have
+        let mut used = vec![0u32; map.tilesets.len()];
+        visit_tiles(&map.layers, &mut |tile| {
+            let count = &mut used[tile.get_tilemap_idx() as usize];
+            *count = (*count).max(tile.tile_id() as u32 + 1);
+        });
+
+        let mut tilesets = Vec::new();
+        let mut first_gid = 1;
+        for (i, pd_path) in map.tilesets.iter().enumerate() {
+            let tileset_relative = Self::relative(pd_path);
+            let rebuilt = load_archive::<ArchivedTileset>(&self.export_root.join(&tileset_relative))
+                .and_then(|tileset| {
+                    self.write_tileset(tileset.access(), &tileset_relative, Some(tile_size), used[i])
+                });
+            let tile_count = match rebuilt {
+                Ok((_, tile_count)) => tile_count,
+                Err(err) => {
+                    println!("{pd_path}: {err:#}, referencing it without rebuilding it");
+                    used[i]
+                }
+            };
+
+            let source = relative_path(dir, &tileset_relative.with_extension("tsx"));
+            tilesets.push((first_gid, source));
+            first_gid += tile_count;
+        }
+        let first_gids: Vec<u32> = tilesets.iter().map(|(first_gid, _)| *first_gid).collect();
+
+        let (width, height) = (map.width.to_native(), map.height.to_native());
+        let orientation = match map.orientation {
+            ArchivedOrientation::Orthogonal => "orthogonal",
+            ArchivedOrientation::Isometric => "isometric",
+            ArchivedOrientation::Staggered => "staggered",
+            ArchivedOrientation::Hexagonal => "hexagonal",
+        };
+        let mut writer = MapWriter {
+            reverse: self,
+            dir,
+            first_gids: &first_gids,
+            tile_size,
+            next_layer_id: max_layer_id(&map.layers) + 1,
+            next_object_id: max_object_id(&map.layers) + 1,
+        };
+
+        // the next layer and object ids are only known once the layers (and the layers added for
+        // baked collision) are written
+        let mut layers = Xml {
+            out: String::new(),
+            depth: 1,
+        };
+        for layer in map.layers.iter() {
+            writer.write_layer(&mut layers, layer);
+        }
+
+        let background = map.background_color.as_ref().map(hex_color);
+        let mut attributes: Vec<(&str, &dyn Display)> = vec![
+            ("version", &"1.10"),
+            ("orientation", &orientation),
+            ("renderorder", &"right-down"),
+            ("width", &width),
+            ("height", &height),
+            ("tilewidth", &tile_size.0),
+            ("tileheight", &tile_size.1),
+            ("infinite", &(map.infinite as u8)),
+        ];
+        if let Some(background) = &background {
+            attributes.push(("backgroundcolor", background));
+        }
+        attributes.extend([
+            ("nextlayerid", &writer.next_layer_id as &dyn Display),
+            ("nextobjectid", &writer.next_object_id),
+        ]);
+
+        let mut xml = Xml::new();
+        xml.open("map", &attributes);
+        write_properties(&mut xml, &map.properties, &[]);
+        for (first_gid, source) in &tilesets {
+            xml.empty("tileset", &[("firstgid", first_gid), ("source", source)]);
+        }
+        xml.out.push_str(&layers.out);
+        xml.close("map");
+
+        self.write(&tmx, xml.out)
+    }
+
+    /// Rebuilds a tileset, returning where it was written and how many tiles it has.
+    ///
+    /// The tile size is read from the name of the image table (`name-table-W-H.png`), or taken
+    /// from `tile_size` if the table isn't in the export folder. The tileset gets at least
+    /// `min_tile_count` tiles, so every tile a map uses exists.
+    fn write_tileset(
+        &self,
+        tileset: &ArchivedTileset,
+        relative: &Path,
+        tile_size: Option<(u32, u32)>,
+        min_tile_count: u32,
+    ) -> anyhow::Result<(PathBuf, u32)> {
+        static IMAGE_TABLE_REGEX: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r#"^(?<name>.*)-table-(?<w>\d+)-(?<h>\d+)\.png$"#).unwrap());
+
+        let tsx = relative.with_extension("tsx");
+        let dir = tsx.parent().unwrap_or(Path::new(""));
+
+        // the archive only has the path the Playdate loads the table with, without the suffix
+        let image = Self::relative(&tileset.image_path);
+        let image_dir = image.parent().unwrap_or(Path::new(""));
+        let image_name = image.file_name().and_then(OsStr::to_str).unwrap_or_default();
+        let table = std::fs::read_dir(self.export_root.join(image_dir))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .find_map(|entry| {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let captures = IMAGE_TABLE_REGEX.captures(&file_name)?;
+                (&captures["name"] == image_name).then(|| {
+                    let size = (captures["w"].parse().unwrap(), captures["h"].parse().unwrap());
+                    (image_dir.join(&file_name), size)
+                })
+            });
+
+        let (tile_width, tile_height) = match (&table, tile_size) {
+            (Some((_, size)), _) => *size,
+            (None, Some(size)) => size,
+            (None, None) => bail!(
+                "{}: can't find the image table of {:?} to get the tile size",
+                relative.display(),
+                tileset.image_path.as_str()
+            ),
+        };
+
+        let mut columns = 1;
+        let mut tile_count = (tileset.tiles.len() as u32).max(min_tile_count);
+        let mut image_element = None;
+        if let Some((table, _)) = &table {
+            let (width, height) = image::image_dimensions(self.export_root.join(table))?;
+            columns = (width / tile_width).max(1);
+            tile_count = tile_count.max(columns * (height / tile_height));
+            self.copy(table)?;
+            image_element = Some((relative_path(dir, table), width, height));
+        }
+
+        let mut xml = Xml::new();
+        let name = tsx.file_stem().unwrap().to_string_lossy().to_string();
+        xml.open(
+            "tileset",
+            &[
+                ("version", &"1.10"),
+                ("name", &name),
+                ("tilewidth", &tile_width),
+                ("tileheight", &tile_height),
+                ("tilecount", &tile_count),
+                ("columns", &columns),
+            ],
+        );
+        if let Some((source, width, height)) = &image_element {
+            xml.empty("image", &[("source", source), ("width", width), ("height", height)]);
+        }
+        for (id, tile) in tileset.tiles.iter().enumerate() {
+            if tile.properties.is_empty() && tile.animation.is_none() {
+                continue;
+            }
+
+            xml.open("tile", &[("id", &id)]);
+            write_properties(&mut xml, &tile.properties, &[]);
+            if let Some(frames) = tile.animation.as_ref() {
+                xml.open("animation", &[]);
+                for frame in frames.iter() {
+                    xml.empty(
+                        "frame",
+                        &[
+                            ("tileid", &frame.tile_id.to_native()),
+                            ("duration", &frame.duration.to_native()),
+                        ],
+                    );
+                }
+                xml.close("animation");
+            }
+            xml.close("tile");
+        }
+        xml.close("tileset");
+
+        Ok((self.write(&tsx, xml.out)?, tile_count))
+    }
+}
+
+struct MapWriter<'a> {
+    reverse: &'a Reverse,
+    /// Folder of the map, relative to the output folder.
+    dir: &'a Path,
+    first_gids: &'a [u32],
+    tile_size: (u32, u32),
+    next_layer_id: u32,
+    next_object_id: u32,
+}
+
+impl MapWriter<'_> {
+    fn gid(&self, tile: WideTile) -> u32 {
+        let mut gid = self.first_gids[tile.get_tilemap_idx() as usize] + tile.tile_id() as u32;
+        if tile.get_flip_x() {
+            gid |= FLIPPED_HORIZONTALLY;
+        }
+        if tile.get_flip_y() {
+            gid |= FLIPPED_VERTICALLY;
+        }
+        if tile.get_flip_d() {
+            gid |= FLIPPED_DIAGONALLY;
+        }
+        gid
+    }
+
+    fn csv(&self, tiles: &ArchivedTileStorage, width: usize) -> String {
+        let gids: Vec<String> = tiles
+            .iter()
+            .map(|tile| tile.map_or(0, |tile| self.gid(tile)).to_string())
+            .collect();
+        let rows: Vec<String> = gids.chunks(width.max(1)).map(|row| row.join(",")).collect();
+        format!("\n{}\n", rows.join(",\n"))
+    }
+
+    fn write_layer(&mut self, xml: &mut Xml, layer: &ArchivedLayer) {
+        let id = layer.id.to_native();
+        let name = layer.name.as_str();
+        let x = layer.x.to_native();
+        let y = layer.y.to_native();
+        let hidden = !layer.visible;
+        let mut attributes: Vec<(&str, &dyn Display)> =
+            vec![("id", &id), ("name", &name), ("offsetx", &x), ("offsety", &y)];
+        if hidden {
+            attributes.push(("visible", &0));
+        }
+        let parallax_x = layer.parallax_x.to_native();
+        let parallax_y = layer.parallax_y.to_native();
+        let opacity = layer.opacity.to_native();
+        if parallax_x != 1.0 {
+            attributes.push(("parallaxx", &parallax_x));
+        }
+        if parallax_y != 1.0 {
+            attributes.push(("parallaxy", &parallax_y));
+        }
+        if opacity != 1.0 {
+            attributes.push(("opacity", &opacity));
+        }
+        let tint = layer.tint_color.as_ref().map(hex_color);
+        if let Some(tint) = &tint {
+            attributes.push(("tintcolor", tint));
+        }
+
+        match &layer.layer_data {
+            ArchivedLayerData::FiniteTileLayer(tiles) => {
+                let width = tiles.width.to_native();
+                let height = tiles.height.to_native();
+                attributes.extend([("width", &width as &dyn Display), ("height", &height)]);
+
+                xml.open("layer", &attributes);
+                write_properties(xml, &layer.properties, &[]);
+                let csv = self.csv(&tiles.tiles, width as usize);
+                xml.text("data", &[("encoding", &"csv")], &csv);
+                xml.close("layer");
+
+                if let Some(collision) = tiles.layer_collision.as_ref() {
+                    self.write_collision(xml, layer, [(collision, (0.0, 0.0))]);
+                }
+            }
+            ArchivedLayerData::InfiniteTileLayer(tiles) => {
+                let mut chunks: Vec<((i32, i32), &ArchivedChunkData)> = tiles.chunk_data().collect();
+                chunks.sort_unstable_by_key(|(pos, _)| *pos);
+
+                let xs = chunks.iter().map(|((x, _), _)| *x);
+                let ys = chunks.iter().map(|((_, y), _)| *y);
+                let span = |min: Option<i32>, max: Option<i32>| match (min, max) {
+                    (Some(min), Some(max)) => (max - min + 1) as u32,
+                    _ => 0,
+                };
+                let width = span(xs.clone().min(), xs.max()) * ChunkData::WIDTH;
+                let height = span(ys.clone().min(), ys.max()) * ChunkData::HEIGHT;
+                attributes.extend([("width", &width as &dyn Display), ("height", &height)]);
+
+                xml.open("layer", &attributes);
+                write_properties(xml, &layer.properties, &[]);
+                xml.open("data", &[("encoding", &"csv")]);
+                for ((x, y), chunk) in &chunks {
+                    let csv = self.csv(&chunk.tiles, ChunkData::WIDTH as usize);
+                    xml.text(
+                        "chunk",
+                        &[
+                            ("x", &(x * ChunkData::WIDTH as i32)),
+                            ("y", &(y * ChunkData::HEIGHT as i32)),
+                            ("width", &ChunkData::WIDTH),
+                            ("height", &ChunkData::HEIGHT),
+                        ],
+                        &csv,
+                    );
+                }
+                xml.close("data");
+                xml.close("layer");
+
+                // chunk collision is relative to the chunk
+                let chunk_width = (ChunkData::WIDTH * self.tile_size.0) as f32;
+                let chunk_height = (ChunkData::HEIGHT * self.tile_size.1) as f32;
+                let collision: Vec<_> = chunks
+                    .iter()
+                    .filter_map(|((x, y), chunk)| {
+                        let offset = (*x as f32 * chunk_width, *y as f32 * chunk_height);
+                        chunk.collision.as_ref().map(|collision| (collision, offset))
+                    })
+                    .collect();
+                if !collision.is_empty() {
+                    self.write_collision(xml, layer, collision);
+                }
+            }
+            ArchivedLayerData::ObjectLayer(objects) => {
+                xml.open("objectgroup", &attributes);
+                write_properties(xml, &layer.properties, &[]);
+                for object in objects.objects.iter() {
+                    self.write_object(xml, object);
+                }
+                xml.close("objectgroup");
+            }
+            ArchivedLayerData::ImageLayer(image) => {
+                if image.repeat_x {
+                    attributes.push(("repeatx", &1));
+                }
+                if image.repeat_y {
+                    attributes.push(("repeaty", &1));
+                }
+                xml.open("imagelayer", &attributes);
+                write_properties(xml, &layer.properties, &[]);
+                let mut relative = Reverse::relative(&image.source);
+                relative.set_extension("png");
+                if let Err(err) = self.reverse.copy(&relative) {
+                    println!("layer {id}: {err:#}");
+                }
+                let source = relative_path(self.dir, &relative);
+                xml.empty(
+                    "image",
+                    &[
+                        ("source", &source),
+                        ("width", &image.width.to_native()),
+                        ("height", &image.height.to_native()),
+                    ],
+                );
+                xml.close("imagelayer");
+            }
+            ArchivedLayerData::GroupLayer(group) => {
+                xml.open("group", &attributes);
+                write_properties(xml, &layer.properties, &[]);
+                for child in group.layers.iter() {
+                    self.write_layer(xml, child);
+                }
+                xml.close("group");
+            }
+        }
+    }
+
+    /// Adds the baked collision of a tile layer as a locked layer of polylines, offset like it.
+    fn write_collision<'c>(
+        &mut self,
+        xml: &mut Xml,
+        layer: &ArchivedLayer,
+        collision: impl IntoIterator<Item = (&'c ArchivedLayerCollision, (f32, f32))>,
+    ) {
+        let id = self.next_layer_id;
+        self.next_layer_id += 1;
+        let name = format!("{} (baked collision)", layer.name.as_str());
+
+        xml.open(
+            "objectgroup",
+            &[
+                ("id", &id),
+                ("name", &name),
+                ("color", &"#ff0000"),
+                ("locked", &1),
+                ("offsetx", &layer.x.to_native()),
+                ("offsety", &layer.y.to_native()),
+            ],
+        );
+        for (collision, (x, y)) in collision {
+            for line in collision.lines.iter() {
+                let id = self.next_object_id;
+                self.next_object_id += 1;
+                xml.open("object", &[("id", &id), ("x", &x), ("y", &y)]);
+                xml.empty("polyline", &[("points", &points(line))]);
+                xml.close("object");
+            }
+        }
+        xml.close("objectgroup");
+    }
+
+    fn write_object(&mut self, xml: &mut Xml, object: &ArchivedObjectData) {
+        let id = object.id.to_native();
+        let name = object.name.as_str();
+        let x = object.x.to_native();
+        let y = object.y.to_native();
+        let mut attributes: Vec<(&str, &dyn Display)> = vec![("id", &id)];
+        if !name.is_empty() {
+            attributes.push(("name", &name));
+        }
+        attributes.extend([("x", &x as &dyn Display), ("y", &y)]);
+        let rotation = object.rotation.to_native();
+        if rotation != 0.0 {
+            attributes.push(("rotation", &rotation));
+        }
+        if !object.visible {
+            attributes.push(("visible", &0));
+        }
+
+        let gid = match &object.shape {
+            ArchivedObjectShape::Tile { tile, .. } => Some(self.gid(*tile)),
+            _ => None,
+        };
+        let size = match &object.shape {
+            ArchivedObjectShape::Tile { width, height, .. }
+            | ArchivedObjectShape::Rect { width, height }
+            | ArchivedObjectShape::Ellipse { width, height } => {
+                Some((width.to_native(), height.to_native()))
+            }
+            ArchivedObjectShape::Text(text) => Some((text.width.to_native(), text.height.to_native())),
+            _ => None,
+        };
+        if let Some(gid) = &gid {
+            attributes.push(("gid", gid));
+        }
+        if let Some((width, height)) = &size {
+            attributes.extend([("width", width as &dyn Display), ("height", height)]);
+        }
+
+        // the font of text objects was taken out of the properties when converting
+        let font = match &object.shape {
+            ArchivedObjectShape::Text(text) => text.font.as_ref().map(|font| font.as_str()),
+            _ => None,
+        };
+        let font: Vec<_> = font.map(|font| ("font", "file", font)).into_iter().collect();
+
+        xml.open("object", &attributes);
+        write_properties(xml, &object.properties, &font);
+        match &object.shape {
+            ArchivedObjectShape::Tile { .. } | ArchivedObjectShape::Rect { .. } => {}
+            ArchivedObjectShape::Ellipse { .. } => xml.empty("ellipse", &[]),
+            ArchivedObjectShape::Point(..) => xml.empty("point", &[]),
+            ArchivedObjectShape::Polyline { points: line } => {
+                xml.empty("polyline", &[("points", &points(line))])
+            }
+            ArchivedObjectShape::Polygon { points: polygon } => {
+                xml.empty("polygon", &[("points", &points(polygon))])
+            }
+            ArchivedObjectShape::Text(text) => {
+                let halign = match text.halign {
+                    ArchivedHorizontalAlignment::Left => "left",
+                    ArchivedHorizontalAlignment::Center => "center",
+                    ArchivedHorizontalAlignment::Right => "right",
+                    ArchivedHorizontalAlignment::Justify => "justify",
+                };
+                let valign = match text.valign {
+                    ArchivedVerticalAlignment::Top => "top",
+                    ArchivedVerticalAlignment::Center => "center",
+                    ArchivedVerticalAlignment::Bottom => "bottom",
+                };
+                xml.text(
+                    "text",
+                    &[
+                        ("fontfamily", &text.font_family.as_str()),
+                        ("pixelsize", &text.pixel_size.to_native()),
+                        ("wrap", &(text.wrap as u8)),
+                        ("halign", &halign),
+                        ("valign", &valign),
+                    ],
+                    &escape(&text.text),
+                );
+            }
+        }
+        xml.close("object");
+    }
+}
+
+/// Writes the properties, followed by `extra` properties given as `(name, type, value)`.
+fn write_properties(xml: &mut Xml, properties: &ArchivedProperties, extra: &[(&str, &str, &str)]) {
+    if properties.is_empty() && extra.is_empty() {
+        return;
+    }
+
+    let mut properties: Vec<_> = properties.iter().collect();
+    properties.sort_unstable_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+
+    xml.open("properties", &[]);
+    for (name, value) in properties {
+        let name = name.as_str();
+        let (kind, value): (&str, String) = match value {
+            ArchivedPropertyValue::BoolValue(value) => ("bool", value.to_string()),
+            ArchivedPropertyValue::FloatValue(value) => ("float", value.to_native().to_string()),
+            ArchivedPropertyValue::IntValue(value) => ("int", value.to_native().to_string()),
+            ArchivedPropertyValue::ColorValue(color) => ("color", hex_color(color)),
+            ArchivedPropertyValue::StringValue(value) => ("string", value.to_string()),
+            ArchivedPropertyValue::FileValue(value) => ("file", value.to_string()),
+            ArchivedPropertyValue::ObjectValue(id) => ("object", id.to_native().to_string()),
+            ArchivedPropertyValue::ClassValue {
+                property_type,
+                properties,
+            } => {
+                xml.open(
+                    "property",
+                    &[
+                        ("name", &name),
+                        ("type", &"class"),
+                        ("propertytype", &property_type.as_str()),
+                    ],
+                );
+                write_properties(xml, properties, &[]);
+                xml.close("property");
+                continue;
+            }
+        };
+
+        xml.empty("property", &[("name", &name), ("type", &kind), ("value", &value)]);
+    }
+    for (name, kind, value) in extra {
+        xml.empty("property", &[("name", name), ("type", kind), ("value", value)]);
+    }
+    xml.close("properties");
+}
+
+/// Formats a color the same as Tiled, `#AARRGGBB`.
+fn hex_color(color: &ArchivedColor) -> String {
+    format!(
+        "#{:02x}{:02x}{:02x}{:02x}",
+        color.alpha, color.red, color.green, color.blue
+    )
+}
+
+/// Calls `f` with every tile of the layers, including tile objects and nested layers.
+fn visit_tiles(layers: &[ArchivedLayer], f: &mut impl FnMut(WideTile)) {
+    for layer in layers {
+        match &layer.layer_data {
+            ArchivedLayerData::FiniteTileLayer(tiles) => tiles.tiles.iter().flatten().for_each(&mut *f),
+            ArchivedLayerData::InfiniteTileLayer(tiles) => tiles
+                .chunk_data()
+                .for_each(|(_, chunk)| chunk.tiles.iter().flatten().for_each(&mut *f)),
+            ArchivedLayerData::ObjectLayer(objects) => {
+                for object in objects.objects.iter() {
+                    if let ArchivedObjectShape::Tile { tile, .. } = &object.shape {
+                        f(*tile);
+                    }
+                }
+            }
+            ArchivedLayerData::GroupLayer(group) => visit_tiles(&group.layers, f),
+            ArchivedLayerData::ImageLayer(_) => {}
+        }
+    }
+}
+
+fn max_layer_id(layers: &[ArchivedLayer]) -> u32 {
+    layers
+        .iter()
+        .map(|layer| match &layer.layer_data {
+            ArchivedLayerData::GroupLayer(group) => {
+                layer.id.to_native().max(max_layer_id(&group.layers))
+            }
+            _ => layer.id.to_native(),
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn max_object_id(layers: &[ArchivedLayer]) -> u32 {
+    layers
+        .iter()
+        .map(|layer| match &layer.layer_data {
+            ArchivedLayerData::ObjectLayer(objects) => objects
+                .objects
+                .iter()
+                .map(|object| object.id.to_native())
+                .max()
+                .unwrap_or(0),
+            ArchivedLayerData::GroupLayer(group) => max_object_id(&group.layers),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn points(points: &[ArchivedTuple2<ArchivedF32, ArchivedF32>]) -> String {
+    let points: Vec<String> = points
+        .iter()
+        .map(|point| format!("{},{}", point.0.to_native(), point.1.to_native()))
+        .collect();
+    points.join(" ")
+}
+
+/// `to` relative to the folder `from`, both relative to the same folder.
+fn relative_path(from: &Path, to: &Path) -> String {
+    let mut path = "../".repeat(from.components().count());
+    path.push_str(&to_slash(to));
+    path
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "&#10;")
+}
+
+/// Just enough of an XML writer for TMX and TSX files.
+struct Xml {
+    out: String,
+    depth: usize,
+}
+
+impl Xml {
+    fn new() -> Self {
+        Self {
+            out: "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n".to_string(),
+            depth: 0,
+        }
+    }
+
+    fn start(&mut self, tag: &str, attributes: &[(&str, &dyn Display)]) {
+        write!(self.out, "{}<{tag}", " ".repeat(self.depth)).unwrap();
+        for (name, value) in attributes {
+            write!(self.out, " {name}=\"{}\"", escape(&value.to_string())).unwrap();
+        }
+    }
+
+    fn open(&mut self, tag: &str, attributes: &[(&str, &dyn Display)]) {
+        self.start(tag, attributes);
+        self.out.push_str(">\n");
+        self.depth += 1;
+    }
+
+    fn empty(&mut self, tag: &str, attributes: &[(&str, &dyn Display)]) {
+        self.start(tag, attributes);
+        self.out.push_str("/>\n");
+    }
+
+    /// An element with text (already escaped) in it.
+    fn text(&mut self, tag: &str, attributes: &[(&str, &dyn Display)], text: &str) {
+        self.start(tag, attributes);
+        writeln!(self.out, ">{text}</{tag}>").unwrap();
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        writeln!(self.out, "{}</{tag}>", " ".repeat(self.depth)).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::encode_archive;
+    use crate::path::to_playdate;
+    use crate::pdtiled::reverse::Reverse;
+    use crate::pdtiled::{convert_map, convert_tileset};
+    use pd_asset::dependencies::AddDependenciesMut;
+    use pd_asset::header::AssetKind;
+    use pd_asset::tilemap::ArchivedTilemap;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tiled::{LayerTileData, Loader, Map, TileLayer};
+
+    /// The files `test-map.edit.tmx` uses, copied so the baked layer isn't written to the assets.
+    const FILES: &[&str] = &[
+        "test-map.edit.tmx",
+        "tiles.tsx",
+        "tiles-table-16-16.png",
+        "folder/tiles-copy.png",
+    ];
+
+    fn copy(from: &Path, to: &Path) {
+        fs::create_dir_all(to.parent().unwrap()).unwrap();
+        fs::copy(from, to).unwrap();
+    }
+
+    /// `(tileset, id, flip_h, flip_v, flip_d)` of every tile of the tile layers.
+    fn tiles(map: &Map) -> Vec<Option<(usize, u32, bool, bool, bool)>> {
+        let tile = |tile: &LayerTileData| {
+            (tile.tileset_index(), tile.id(), tile.flip_h, tile.flip_v, tile.flip_d)
+        };
+        let mut tiles = Vec::new();
+        for layer in map.layers() {
+            let Some(TileLayer::Finite(layer)) = layer.as_tile_layer() else {
+                continue;
+            };
+            for y in 0..layer.height() as i32 {
+                for x in 0..layer.width() as i32 {
+                    tiles.push(layer.get_tile_data(x, y).map(tile));
+                }
+            }
+        }
+        tiles
+    }
+
+    #[test]
+    pub fn map_roundtrip() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets");
+        let dir = std::env::temp_dir().join(format!("editor-reverse-{}", std::process::id()));
+        let (source, export, out) = (dir.join("source"), dir.join("export"), dir.join("out"));
+        for file in FILES {
+            copy(&assets.join(file), &source.join(file));
+        }
+
+        // what the build exports: archives with Playdate paths, and the images
+        let load_map = || Loader::new().load_tmx_map(source.join("test-map.edit.tmx")).unwrap();
+        let original = load_map();
+        let mut map = convert_map(load_map());
+        let mut paths = Vec::new();
+        map.add_dependencies_mut(&mut paths);
+        for path in paths {
+            // baked layer images are already relative to the map
+            let absolute = PathBuf::from(path.as_str());
+            let relative = absolute.strip_prefix(&source).unwrap_or(&absolute).to_path_buf();
+            let extension = match relative.extension().unwrap().to_str().unwrap() {
+                "tsx" => "tsb",
+                _ => "pdi",
+            };
+            *path = to_playdate(&relative.with_extension(extension));
+        }
+        let map_bytes = pd_asset::rkyv::to_bytes::<pd_asset::RkyvError>(&map).unwrap();
+
+        let tileset = Loader::new().load_tsx_tileset(source.join("tiles.tsx")).unwrap();
+        let mut tileset = convert_tileset(tileset);
+        tileset.image_path = to_playdate(Path::new("tiles"));
+        let tileset_bytes = pd_asset::rkyv::to_bytes::<pd_asset::RkyvError>(&tileset).unwrap();
+        fs::create_dir_all(&export).unwrap();
+        let tileset_bytes = encode_archive(AssetKind::Tileset, &tileset_bytes);
+        fs::write(export.join("tiles.tsb"), tileset_bytes).unwrap();
+        copy(&source.join("tiles-table-16-16.png"), &export.join("tiles-table-16-16.png"));
+        copy(&source.join("folder/tiles-copy.png"), &export.join("folder/tiles-copy.png"));
+
+        let reverse = Reverse {
+            export_root: export,
+            out_root: out,
+        };
+        let map =
+            pd_asset::rkyv::access::<ArchivedTilemap, pd_asset::RkyvError>(&map_bytes).unwrap();
+        let tmx = reverse.write_map(map, Path::new("test-map.edit.tmb")).unwrap();
+        let rebuilt = Loader::new().load_tmx_map(&tmx).unwrap();
+
+        let ids = |map: &Map| map.layers().map(|layer| layer.id()).collect::<Vec<_>>();
+        assert_eq!(ids(&rebuilt), ids(&original));
+        assert_eq!(tiles(&rebuilt), tiles(&original));
+        assert!(tiles(&original).iter().flatten().any(|tile| tile.2 && tile.3 && tile.4));
+
+        let objects = |map: &Map| {
+            let layer = map.layers().find_map(|layer| layer.as_object_layer()).unwrap();
+            layer.objects().map(|object| (*object).clone()).collect::<Vec<_>>()
+        };
+        let (rebuilt_objects, original_objects) = (objects(&rebuilt), objects(&original));
+        assert_eq!(rebuilt_objects.len(), original_objects.len());
+        for (rebuilt, original) in rebuilt_objects.iter().zip(&original_objects) {
+            assert_eq!(rebuilt.id(), original.id());
+            assert_eq!(rebuilt.name, original.name);
+            assert_eq!(
+                (rebuilt.x, rebuilt.y, rebuilt.rotation),
+                (original.x, original.y, original.rotation)
+            );
+            assert_eq!(rebuilt.shape, original.shape);
+            assert_eq!(rebuilt.properties, original.properties);
+
+            let tile = |object: &tiled::ObjectData| {
+                object.tile_data().map(|tile| (tile.id(), tile.flip_h, tile.flip_v, tile.flip_d))
+            };
+            assert_eq!(tile(rebuilt), tile(original));
+        }
+        // the camera is a class property with a nested class
+        assert!(original_objects.iter().any(|object| object.properties.contains_key("camera")));
+
+        let image = |map: &Map| {
+            let layer = map.layers().find_map(|layer| layer.as_image_layer()).unwrap();
+            layer.image.as_ref().unwrap().source.file_name().unwrap().to_owned()
+        };
+        assert_eq!(image(&rebuilt), image(&original));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
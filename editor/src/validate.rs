@@ -0,0 +1,545 @@
+//! Checks the class properties of maps and tilesets against `type-export.json`, the types the game
+//! reflects and writes with `export_types`. The game only finds out about a misspelled type or a
+//! wrong value when it loads the map (and then skips the component), so the editor checks every
+//! archive before writing it.
+
+use pd_asset::properties::{Properties, PropertyValue};
+use pd_asset::tilemap::{Layer, LayerData, Tilemap};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use anyhow::Context;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Name of the file `export_types` writes the registry to, in the data folder of the game.
+pub const TYPE_EXPORT_PATH: &str = "type-export.json";
+
+/// Where the simulator keeps the registry the game exported: the simulator gives every game a
+/// data folder at `Disk/Data/<bundle id>` in the SDK. Overridden by the `type-export` key of the
+/// manifest.
+pub fn simulator_type_export() -> anyhow::Result<PathBuf> {
+    let sdk = std::env::var_os("PLAYDATE_SDK_PATH").context(
+        "PLAYDATE_SDK_PATH isn't set, set it or the type-export key of the manifest to find \
+         the types the game exported",
+    )?;
+    let game_toml = std::fs::read_to_string(crate::GAME_TOML)?;
+    let game_toml = toml_edit::DocumentMut::from_str(&game_toml)?;
+    let bundle_id = game_toml["package"]["metadata"]["playdate"]["bundle-id"]
+        .as_str()
+        .context("game has no bundle-id")?;
+
+    Ok(PathBuf::from(sdk)
+        .join("Disk")
+        .join("Data")
+        .join(bundle_id)
+        .join(TYPE_EXPORT_PATH))
+}
+
+/// Name of the member holding the variant of an enum with non-unit variants.
+const VARIANT_MEMBER: &str = ":variant";
+
+/// The subset of the game's `types_json` format needed for validation.
+#[derive(Deserialize)]
+struct TypeExport {
+    name: String,
+    #[serde(flatten)]
+    type_data: TypeData,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum TypeData {
+    Enum(Enum),
+    Class(Class),
+}
+
+#[derive(Deserialize)]
+struct Class {
+    members: Vec<Member>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Member {
+    name: String,
+    property_type: Option<String>,
+    #[serde(rename = "type")]
+    type_field: FieldType,
+    #[serde(default)]
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Enum {
+    storage_type: StorageType,
+    values: Vec<String>,
+    values_as_flags: bool,
+}
+
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum StorageType {
+    String,
+    Int,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FieldType {
+    Bool,
+    Color,
+    Float,
+    File,
+    Int,
+    Object,
+    String,
+    Class,
+}
+
+/// A property that the game would fail to load.
+#[derive(Debug)]
+pub struct PropertyError {
+    /// The map or tileset, and the layer, object or tile in it.
+    pub location: String,
+    /// The path to the member, e.g. `Health.max`.
+    pub property: String,
+    pub message: String,
+}
+
+impl Display for PropertyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: `{}`: {}", self.location, self.property, self.message)
+    }
+}
+
+pub struct TypeRegistry {
+    types: HashMap<String, TypeData>,
+}
+
+impl TypeRegistry {
+    /// Reads the registry at `path`, or returns `None` if the game hasn't exported it yet.
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Self::parse(&bytes).map(Some)
+    }
+
+    fn parse(json: &[u8]) -> anyhow::Result<Self> {
+        let types: Vec<TypeExport> = serde_json::from_slice(json)?;
+        let types = types.into_iter().map(|t| (t.name, t.type_data)).collect();
+
+        Ok(Self { types })
+    }
+
+    /// Checks the properties of the map and of every layer and object in it.
+    pub fn check_tilemap(&self, file: &Path, map: &Tilemap) -> Vec<PropertyError> {
+        let file = file.display().to_string();
+        let mut errors = Vec::new();
+        self.check_properties(&file, &map.properties, &mut errors);
+        for layer in &map.layers {
+            self.check_layer(&file, layer, &mut errors);
+        }
+        errors
+    }
+
+    fn check_layer(&self, file: &str, layer: &Layer, errors: &mut Vec<PropertyError>) {
+        let location = format!("{file}: layer {} ({})", layer.id, layer.name);
+        self.check_properties(&location, &layer.properties, errors);
+
+        match &layer.layer_data {
+            LayerData::GroupLayer(group) => {
+                for layer in &group.layers {
+                    self.check_layer(file, layer, errors);
+                }
+            }
+            LayerData::ObjectLayer(objects) => {
+                for object in &objects.objects {
+                    let location = format!("{location}: object {} ({})", object.id, object.name);
+                    self.check_properties(&location, &object.properties, errors);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks the properties of the tile `tile_id` of the tileset at `file`.
+    pub fn check_tile(&self, file: &Path, tile_id: u32, properties: &Properties) -> Vec<PropertyError> {
+        let location = format!("{}: tile {tile_id}", file.display());
+        let mut errors = Vec::new();
+        self.check_properties(&location, properties, &mut errors);
+        errors
+    }
+
+    /// Only class properties are loaded as components and resources, the rest are read by the
+    /// editor itself (e.g. `collision` on tile layers).
+    fn check_properties(&self, location: &str, properties: &Properties, errors: &mut Vec<PropertyError>) {
+        let mut properties: Vec<_> = properties.iter().collect();
+        properties.sort_unstable_by_key(|(name, _)| name.as_str());
+
+        for (name, value) in properties {
+            let PropertyValue::ClassValue { property_type, properties } = value else {
+                continue;
+            };
+            let mut check = Check { types: self, location, errors };
+            check.class(property_type, properties, name, &serde_json::Value::Null);
+        }
+    }
+}
+
+/// The state of checking one top level property.
+struct Check<'a> {
+    types: &'a TypeRegistry,
+    location: &'a str,
+    errors: &'a mut Vec<PropertyError>,
+}
+
+impl Check<'_> {
+    fn error(&mut self, property: &str, message: String) {
+        self.errors.push(PropertyError {
+            location: self.location.to_string(),
+            property: property.to_string(),
+            message,
+        });
+    }
+
+    /// `defaults` are the member values of the enclosing class' default, if it has one.
+    fn class(&mut self, type_name: &str, properties: &Properties, path: &str, defaults: &serde_json::Value) {
+        let class = match self.types.types.get(type_name) {
+            Some(TypeData::Class(class)) => class,
+            Some(TypeData::Enum(_)) => {
+                return self.error(path, format!("`{type_name}` is an enum, not a class"));
+            }
+            None => return self.error(path, format!("unknown type `{type_name}`")),
+        };
+
+        if class.members.iter().any(|member| member.name == VARIANT_MEMBER) {
+            return self.variant_class(class, properties, path);
+        }
+
+        for member in &class.members {
+            let member_path = format!("{path}.{}", member.name);
+            let default = match defaults.get(&member.name) {
+                Some(default) if !default.is_null() => default,
+                _ => &member.value,
+            };
+            match properties.get(&member.name) {
+                Some(value) => self.member(member, value, &member_path, default),
+                None if default.is_null() && self.is_required(member) => {
+                    self.error(&member_path, format!(
+                        "missing required member of type `{}` with no default value",
+                        member.property_type.as_deref().unwrap_or_default(),
+                    ));
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Enums with non-unit variants are exported as a class holding the name of the variant and
+    /// a class member for each non-unit variant. Only the selected variant is loaded.
+    fn variant_class(&mut self, class: &Class, properties: &Properties, path: &str) {
+        let variant = class.members.iter().find(|member| member.name == VARIANT_MEMBER).unwrap();
+        let variant_path = format!("{path}.{VARIANT_MEMBER}");
+        let Some(value) = properties.get(VARIANT_MEMBER) else {
+            return;
+        };
+        self.member(variant, value, &variant_path, &variant.value);
+
+        let PropertyValue::StringValue(selected) = value else {
+            return;
+        };
+        let Some(member) = class.members.iter().find(|member| &member.name == selected) else {
+            return;
+        };
+        let member_path = format!("{path}.{selected}");
+        match properties.get(selected) {
+            Some(value) => self.member(member, value, &member_path, &member.value),
+            None => {
+                if let Some(type_name) = &member.property_type {
+                    self.class(type_name, &Properties::new(), &member_path, &member.value);
+                }
+            }
+        }
+    }
+
+    fn member(&mut self, member: &Member, value: &PropertyValue, path: &str, default: &serde_json::Value) {
+        if let Some(type_name) = &member.property_type
+            && let Some(TypeData::Enum(e)) = self.types.types.get(type_name)
+        {
+            return self.enum_value(type_name, e, value, path);
+        }
+
+        use PropertyValue as PV;
+        match (member.type_field, value) {
+            (FieldType::Bool, PV::BoolValue(_))
+            | (FieldType::Int, PV::IntValue(_))
+            | (FieldType::Float, PV::FloatValue(_) | PV::IntValue(_))
+            | (FieldType::String, PV::StringValue(_))
+            | (FieldType::Color, PV::ColorValue(_))
+            | (FieldType::File, PV::FileValue(_))
+            | (FieldType::Object, PV::ObjectValue(_)) => {}
+            (FieldType::Class, PV::ClassValue { property_type, properties }) => {
+                let Some(expected) = &member.property_type else {
+                    return self.error(path, format!("unexpected class `{property_type}`"));
+                };
+                if property_type != expected {
+                    return self.error(path, format!("expected class `{expected}`, found `{property_type}`"));
+                }
+                self.class(expected, properties, path, default);
+            }
+            (expected, value) => {
+                self.error(path, format!("expected {expected:?}, found {}", kind_name(value)));
+            }
+        }
+    }
+
+    fn enum_value(&mut self, type_name: &str, e: &Enum, value: &PropertyValue, path: &str) {
+        match (e.storage_type, value) {
+            (StorageType::String, PropertyValue::StringValue(s)) => {
+                let values: Vec<&str> = if e.values_as_flags {
+                    s.split(',').filter(|v| !v.is_empty()).collect()
+                } else {
+                    vec![s.as_str()]
+                };
+                for v in values {
+                    if !e.values.iter().any(|variant| variant == v) {
+                        self.error(path, format!("`{type_name}` has no variant `{v}`"));
+                    }
+                }
+            }
+            (StorageType::Int, PropertyValue::IntValue(i)) => {
+                let count = e.values.len() as u32;
+                let valid = if e.values_as_flags {
+                    count >= 32 || (*i as u32) >> count == 0
+                } else {
+                    (0..count as i32).contains(i)
+                };
+                if !valid {
+                    self.error(path, format!("`{i}` is not a valid value of `{type_name}`"));
+                }
+            }
+            (StorageType::String, value) => {
+                self.error(path, format!("expected a variant of `{type_name}`, found {}", kind_name(value)));
+            }
+            (StorageType::Int, value) => {
+                self.error(path, format!("expected the index of a variant of `{type_name}`, found {}", kind_name(value)));
+            }
+        }
+    }
+
+    /// Whether the game fails to load a class without this member. Primitive members fall back to
+    /// the default of their type, classes only if every member of theirs has a default.
+    fn is_required(&self, member: &Member) -> bool {
+        if !matches!(member.type_field, FieldType::Class) {
+            return false;
+        }
+        match member.property_type.as_ref().and_then(|name| self.types.types.get(name)) {
+            Some(TypeData::Class(class)) => {
+                !class.members.is_empty() && class.members.iter().all(|member| member.value.is_null())
+            }
+            _ => false,
+        }
+    }
+}
+
+fn kind_name(value: &PropertyValue) -> &'static str {
+    match value {
+        PropertyValue::BoolValue(_) => "Bool",
+        PropertyValue::FloatValue(_) => "Float",
+        PropertyValue::IntValue(_) => "Int",
+        PropertyValue::ColorValue(_) => "Color",
+        PropertyValue::StringValue(_) => "String",
+        PropertyValue::FileValue(_) => "File",
+        PropertyValue::ObjectValue(_) => "Object",
+        PropertyValue::ClassValue { .. } => "Class",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::validate::TypeRegistry;
+    use pd_asset::properties::{Properties, PropertyValue};
+    use std::path::Path;
+
+    const TYPES: &str = r#"[
+        { "name": "Health", "type": "class", "members": [
+            { "name": "max", "type": "int", "value": 3 },
+            { "name": "regen", "type": "float" }
+        ] },
+        { "name": "Target", "type": "class", "members": [
+            { "name": "object", "type": "object" }
+        ] },
+        { "name": "Follow", "type": "class", "members": [
+            { "name": "target", "type": "class", "propertyType": "Target" },
+            { "name": "health", "type": "class", "propertyType": "Health" }
+        ] },
+        { "name": "Team", "type": "enum", "storageType": "string",
+          "values": ["Red", "Blue"], "valuesAsFlags": false },
+        { "name": "Layers", "type": "enum", "storageType": "string",
+          "values": ["Ground", "Air"], "valuesAsFlags": true },
+        { "name": "Level", "type": "enum", "storageType": "int",
+          "values": ["Easy", "Hard"], "valuesAsFlags": false },
+        { "name": "Mask", "type": "enum", "storageType": "int",
+          "values": ["Ground", "Air"], "valuesAsFlags": true },
+        { "name": "Unit", "type": "class", "members": [
+            { "name": "team", "type": "string", "propertyType": "Team" },
+            { "name": "layers", "type": "string", "propertyType": "Layers" },
+            { "name": "level", "type": "int", "propertyType": "Level" },
+            { "name": "mask", "type": "int", "propertyType": "Mask" }
+        ] },
+        { "name": "Shape:::Variant", "type": "enum", "storageType": "string",
+          "values": ["Point", "Circle"], "valuesAsFlags": false },
+        { "name": "Shape::Circle", "type": "class", "members": [
+            { "name": "radius", "type": "float" }
+        ] },
+        { "name": "Shape", "type": "class", "members": [
+            { "name": ":variant", "type": "class", "propertyType": "Shape:::Variant", "value": "Point" },
+            { "name": "Circle", "type": "class", "propertyType": "Shape::Circle" }
+        ] }
+    ]"#;
+
+    fn registry() -> TypeRegistry {
+        TypeRegistry::parse(TYPES.as_bytes()).unwrap()
+    }
+
+    fn class(property_type: &str, members: Vec<(&str, PropertyValue)>) -> PropertyValue {
+        PropertyValue::ClassValue {
+            property_type: property_type.to_string(),
+            properties: members.into_iter().map(|(name, value)| (name.to_string(), value)).collect(),
+        }
+    }
+
+    /// The properties and messages of the errors in a tile with `value` as its only property.
+    fn check(value: PropertyValue) -> Vec<(String, String)> {
+        let properties = Properties::from_iter([("component".to_string(), value)]);
+        registry()
+            .check_tile(Path::new("tileset.tsx"), 0, &properties)
+            .into_iter()
+            .map(|error| (error.property, error.message))
+            .collect()
+    }
+
+    fn string(s: &str) -> PropertyValue {
+        PropertyValue::StringValue(s.to_string())
+    }
+
+    #[test]
+    pub fn valid_class() {
+        let health = class("Health", vec![("max", PropertyValue::IntValue(5))]);
+        assert!(check(health).is_empty());
+
+        // ints are accepted as floats, and primitives fall back to their defaults
+        let health = class("Health", vec![("regen", PropertyValue::IntValue(1))]);
+        assert!(check(health).is_empty());
+    }
+
+    #[test]
+    pub fn unknown_type() {
+        let errors = check(class("Heath", vec![]));
+        assert_eq!(errors, vec![("component".to_string(), "unknown type `Heath`".to_string())]);
+
+        let errors = check(class("Team", vec![]));
+        assert_eq!(errors, vec![("component".to_string(), "`Team` is an enum, not a class".to_string())]);
+    }
+
+    #[test]
+    pub fn missing_required_member() {
+        let errors = check(class("Follow", vec![]));
+        assert_eq!(
+            errors,
+            vec![(
+                "component.target".to_string(),
+                "missing required member of type `Target` with no default value".to_string()
+            )]
+        );
+
+        let target = class("Target", vec![("object", PropertyValue::ObjectValue(4))]);
+        assert!(check(class("Follow", vec![("target", target)])).is_empty());
+    }
+
+    #[test]
+    pub fn wrong_primitive() {
+        let health = class("Health", vec![("max", PropertyValue::FloatValue(2.5))]);
+        assert_eq!(check(health), vec![("component.max".to_string(), "expected Int, found Float".to_string())]);
+
+        let target = class("Health", vec![]);
+        let errors = check(class("Follow", vec![("target", target)]));
+        assert_eq!(
+            errors,
+            vec![("component.target".to_string(), "expected class `Target`, found `Health`".to_string())]
+        );
+    }
+
+    #[test]
+    pub fn enums() {
+        let unit = class("Unit", vec![("team", string("Red")), ("level", PropertyValue::IntValue(1))]);
+        assert!(check(unit).is_empty());
+
+        let unit = class("Unit", vec![("team", string("Green")), ("level", PropertyValue::IntValue(2))]);
+        assert_eq!(
+            check(unit),
+            vec![
+                ("component.team".to_string(), "`Team` has no variant `Green`".to_string()),
+                ("component.level".to_string(), "`2` is not a valid value of `Level`".to_string()),
+            ]
+        );
+
+        let unit = class("Unit", vec![("team", PropertyValue::IntValue(0))]);
+        assert_eq!(
+            check(unit),
+            vec![("component.team".to_string(), "expected a variant of `Team`, found Int".to_string())]
+        );
+    }
+
+    #[test]
+    pub fn flags() {
+        let unit = class("Unit", vec![("layers", string("Ground,Air")), ("mask", PropertyValue::IntValue(3))]);
+        assert!(check(unit).is_empty());
+
+        // no flags set
+        let unit = class("Unit", vec![("layers", string("")), ("mask", PropertyValue::IntValue(0))]);
+        assert!(check(unit).is_empty());
+
+        let unit = class("Unit", vec![("layers", string("Ground,Water")), ("mask", PropertyValue::IntValue(4))]);
+        assert_eq!(
+            check(unit),
+            vec![
+                ("component.layers".to_string(), "`Layers` has no variant `Water`".to_string()),
+                ("component.mask".to_string(), "`4` is not a valid value of `Mask`".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn variant() {
+        // only the selected variant is checked
+        let circle = class("Shape::Circle", vec![("radius", PropertyValue::BoolValue(true))]);
+        let shape = class("Shape", vec![(":variant", string("Point")), ("Circle", circle.clone())]);
+        assert!(check(shape).is_empty());
+
+        let shape = class("Shape", vec![(":variant", string("Circle")), ("Circle", circle)]);
+        assert_eq!(
+            check(shape),
+            vec![("component.Circle.radius".to_string(), "expected Float, found Bool".to_string())]
+        );
+
+        let shape = class("Shape", vec![(":variant", string("Square"))]);
+        assert_eq!(
+            check(shape),
+            vec![("component.:variant".to_string(), "`Shape:::Variant` has no variant `Square`".to_string())]
+        );
+    }
+
+    #[test]
+    pub fn errors_name_location() {
+        let properties = Properties::from_iter([("component".to_string(), class("Heath", vec![]))]);
+        let errors = registry().check_tile(Path::new("tileset.tsx"), 7, &properties);
+        assert_eq!(errors[0].to_string(), "tileset.tsx: tile 7: `component`: unknown type `Heath`");
+    }
+}
@@ -1,7 +1,8 @@
 use bevy_ecs::prelude::{EntityCommands, ReflectComponent};
 use crate::tiled::collision::{Collision, TileLayerCollision};
 use crate::tiled::spawn::MapHandle;
-use crate::tiled::{AssetLoader, JobCommandsExt, Loading, Map, MapLoader, SpriteLoader, SpriteTableLoader};
+use crate::gif::GifLoader;
+use crate::tiled::{add_loader, AssetLoader, JobCommandsExt, Loading, Map, MapLoader, SpriteLoader, SpriteTableLoader};
 use alloc::string::String;
 use alloc::{format, vec};
 use alloc::sync::Arc;
@@ -10,10 +11,12 @@ use bevy_app::{App, Last, Plugin, PostUpdate, Startup, Update};
 use bevy_ecs::component::HookContext;
 use bevy_ecs::prelude::{Children, Commands, Component, Entity, IntoScheduleConfigs, Name, Query, Res, ResMut, Single, With};
 use bevy_ecs::world::DeferredWorld;
+use bevy_ecs::event::EventReader;
 use bevy_input::ButtonInput;
 use bevy_math::{Rot2, Vec2};
 use bevy_reflect::Reflect;
 use bevy_state::prelude::{in_state, NextState, OnEnter, OnExit, State};
+use bevy_playdate::animation::{AnimatedSprite, AnimationFinished, AnimationMode};
 use bevy_playdate::debug::{in_debug, Debug};
 use bevy_playdate::input::{CrankInput, PlaydateButton};
 use bevy_playdate::jobs::{Jobs, JobsScheduler};
@@ -28,7 +31,7 @@ use pd::graphics::color::{Color, LCDColorConst};
 use pd::graphics::text::draw_text;
 use pd::graphics::{fill_rect, Graphics, LineCapStyle};
 use pd::sprite::draw_sprites;
-use pd::sys::ffi::LCDColor;
+use pd::sys::ffi::{LCDBitmapFlip, LCDColor};
 use bevy_playdate::asset::{AssetAsync, AssetCache, ResAssetCache};
 use bevy_playdate::visibility::Visibility;
 use diagnostic::dbg;
@@ -66,6 +69,7 @@ impl Plugin for GamePlugin {
         //     .add_systems(OnExit(LoadingState::NotLoading), || println!("exit start loading"));
         app
             .add_plugins(crate::tiled::job::BatchQueuePlugin);
+        add_loader::<GifLoader>(app);
         
         app
             .add_plugins(crate::state::StatesPlugin)
@@ -73,7 +77,7 @@ impl Plugin for GamePlugin {
             .add_systems(OnEnter(LoadingState::Loading), spawn_despawn_map)
             .add_systems(OnEnter(LoadingState::StartLoading), start_transition_in)
             .add_systems(OnEnter(LoadingState::EndLoading), start_transition_out)
-            .add_systems(Update, move_screen_transition)
+            .add_systems(Update, finish_screen_transition)
             .add_systems(Last, move_after_loading
                 .run_if(in_state(LoadingState::Loading))
                 .after(Jobs::run_jobs_system)
@@ -98,13 +102,9 @@ struct ScreenTransition {
 enum ScreenTransitionState {
     #[default]
     Inactive,
-    MoveIn {
-        t: f32,
-    },
+    MoveIn,
     Stay,
-    MoveOut {
-        t: f32,
-    },
+    MoveOut,
 }
 
 fn spawn_loading_transition(mut commands: Commands) {
@@ -114,74 +114,78 @@ fn spawn_loading_transition(mut commands: Commands) {
         Visibility::Hidden,
         ScreenTransition::default(),
     ))
-        .insert_loading_asset(SpriteLoader {
-            center: [1.0, 0.0],
-            z_index: 10000,
-            ignore_draw_offset: true,
-        }, 0, "assets/transition-simple.pdi");
+        .insert_loading_asset(GifLoader {
+            sprite_loader: SpriteLoader {
+                center: [0.0, 0.0],
+                z_index: 10000,
+                ignore_draw_offset: true,
+                flip: LCDBitmapFlip::kBitmapUnflipped,
+            },
+            mode: AnimationMode::Once,
+            autoplay: false,
+        }, 0, "assets/screen-transition.gifb");
 }
 
+/// Plays the transition over the screen, or goes straight to loading if the transition's
+/// animation isn't loaded (still loading or failed), so loading never waits on it.
 fn start_transition_in(
-    transition: Single<(&mut ScreenTransition, &mut Visibility, &mut Transform)>,
+    transition: Single<(&mut ScreenTransition, &mut Visibility, Option<&mut AnimatedSprite>)>,
+    mut loading_state: ResMut<NextState<LoadingState>>,
 ) {
-    let (mut transition, mut visibility, mut transform) = transition.into_inner();
-    transition.state = ScreenTransitionState::MoveIn { t: 0.0 };
-    transform.x = 0.0;
+    let (mut transition, mut visibility, animation) = transition.into_inner();
+    let Some(mut animation) = animation else {
+        transition.state = ScreenTransitionState::Stay;
+        loading_state.set(LoadingState::Loading);
+        return;
+    };
+
+    transition.state = ScreenTransitionState::MoveIn;
+    animation.reversed = false;
+    animation.restart();
     *visibility = Visibility::Visible;
 }
 
+/// Wipes the transition off the screen, or finishes loading straight away without its
+/// animation.
 fn start_transition_out(
-    transition: Single<&mut ScreenTransition>,
+    transition: Single<(&mut ScreenTransition, &mut Visibility, Option<&mut AnimatedSprite>)>,
+    mut loading_state: ResMut<NextState<LoadingState>>,
 ) {
-    let mut transition = transition.into_inner();
-    transition.state = ScreenTransitionState::MoveOut { t: 0.0 };
+    let (mut transition, mut visibility, animation) = transition.into_inner();
+    let Some(mut animation) = animation else {
+        transition.state = ScreenTransitionState::Inactive;
+        *visibility = Visibility::Hidden;
+        loading_state.set(LoadingState::NotLoading);
+        return;
+    };
+
+    transition.state = ScreenTransitionState::MoveOut;
+    // wipe the screen clear by playing the same frames backwards
+    animation.reversed = true;
+    animation.restart();
 }
 
-fn move_screen_transition(
-    transition: Single<(&mut ScreenTransition, &mut Visibility, &mut Transform)>,
-    time: Res<Time>,
+fn finish_screen_transition(
+    mut finished: EventReader<AnimationFinished>,
+    transition: Single<(Entity, &mut ScreenTransition, &mut Visibility)>,
     mut loading_state: ResMut<NextState<LoadingState>>,
 ) {
-    let (mut transition, mut visibility, mut transform) = transition.into_inner();
-    
-    let initial = 0.0;
-    let target_in = 520.0;
-    let target_out = 1040.0;
-    
-    fn eval(t: f32, start: f32, end: f32) -> f32 {
-        // bevy_math::ops:
-        // let t = (1.0 - (t - 1.0).powi(2)).sqrt();
-        let t = bevy_math::ops::sin(t * core::f32::consts::FRAC_PI_2);
-        bevy_math::FloatExt::lerp(start, end, t)
+    let (entity, mut transition, mut visibility) = transition.into_inner();
+    if !finished.read().any(|event| event.entity == entity) {
+        return;
     }
-    
-    let transition_time = 0.25;
-    
-    match &mut transition.state {
+
+    match transition.state {
         ScreenTransitionState::Inactive => {}
         ScreenTransitionState::Stay => {}
-        ScreenTransitionState::MoveIn { t } => {
-            *t = (*t + time.delta_secs() / transition_time).min(1.0);
-            if *t == 1.0 {
-                transform.x = target_in;
-                transition.state = ScreenTransitionState::Stay;
-                loading_state.set(LoadingState::Loading);
-            } else {
-                let x = eval(*t, initial, target_in);
-                transform.x = x;
-            }
+        ScreenTransitionState::MoveIn => {
+            transition.state = ScreenTransitionState::Stay;
+            loading_state.set(LoadingState::Loading);
         }
-        ScreenTransitionState::MoveOut { t } => {
-            *t = (*t + time.delta_secs() / transition_time).min(1.0);
-            if *t == 1.0 {
-                transform.x = target_out;
-                transition.state = ScreenTransitionState::Inactive;
-                *visibility = Visibility::Hidden;
-                loading_state.set(LoadingState::NotLoading);
-            } else {
-                let x = eval(*t, target_in, target_out);
-                transform.x = x;
-            }
+        ScreenTransitionState::MoveOut => {
+            transition.state = ScreenTransitionState::Inactive;
+            *visibility = Visibility::Hidden;
+            loading_state.set(LoadingState::NotLoading);
         }
     }
 }
@@ -0,0 +1,85 @@
+use alloc::vec::Vec;
+use crate::rkyv::load_compressed_archive;
+use crate::tiled::job::BatchCommands;
+use crate::tiled::{AssetLoader, SpriteLoader};
+use bevy_ecs::entity::Entity;
+use bevy_platform::sync::Arc;
+use bevy_playdate::animation::{AnimatedSprite, AnimationFrame, AnimationMode};
+use bevy_playdate::asset::{AssetAsync, BitmapRef, BitmapTableAsset};
+use bevy_playdate::jobs::{AsyncLoadCtx, GenJobExtensions};
+use diagnostic::dbg;
+use pd_asset::gif::ArchivedGif;
+
+/// An animation exported by the editor: the frames of an image table and the rate to play them at.
+pub struct GifAsset {
+    pub table: Arc<BitmapTableAsset>,
+    pub fps: f32,
+    /// The frames to play with their durations, if not every frame of the table at `fps`.
+    pub frames: Option<Arc<[AnimationFrame]>>,
+}
+
+impl AssetAsync for GifAsset {
+    type Error = anyhow::Error;
+
+    async fn load(load_cx: &mut AsyncLoadCtx, path: &str) -> Result<Self, Self::Error> {
+        let data = load_compressed_archive::<ArchivedGif>(load_cx, path).await?;
+        let fps = data.access().fps.to_native();
+        let frames: Vec<AnimationFrame> = data
+            .access()
+            .frames
+            .iter()
+            .map(|frame| AnimationFrame {
+                index: frame.index.to_native() as usize,
+                duration: frame.duration.to_native() as f32 / 1000.0,
+            })
+            .collect();
+        let frames = (!frames.is_empty()).then(|| Arc::from(frames));
+        let image_path: Arc<str> = Arc::from(data.access().image_path.as_str());
+
+        let table = load_cx
+            .load_asset::<BitmapTableAsset>(image_path.clone())
+            .await
+            .map_err(|err| anyhow::anyhow!("{image_path}: {err:?}"))?;
+
+        Ok(Self { table, fps, frames })
+    }
+}
+
+/// Loads a [`GifAsset`], then inserts a [`Sprite`](bevy_playdate::sprite::Sprite) showing its
+/// first frame and an [`AnimatedSprite`] playing it.
+pub struct GifLoader {
+    pub sprite_loader: SpriteLoader,
+    pub mode: AnimationMode,
+    /// If false, the animation waits for [`AnimatedSprite::restart`].
+    pub autoplay: bool,
+}
+
+impl AssetLoader for GifLoader {
+    type Asset = GifAsset;
+
+    fn on_finish_load(
+        &self,
+        commands: &mut BatchCommands,
+        entity: Entity,
+        result: Result<Arc<Self::Asset>, <<Self as AssetLoader>::Asset as AssetAsync>::Error>,
+    ) {
+        let gif = match result {
+            Ok(gif) => gif,
+            Err(err) => {
+                dbg!(err);
+                return;
+            }
+        };
+
+        let mut animation = AnimatedSprite::new(gif.table.clone(), gif.fps, self.mode);
+        if let Some(frames) = &gif.frames {
+            animation = animation.with_frames(frames.clone());
+        }
+        animation.playing = self.autoplay;
+        let sprite = self
+            .sprite_loader
+            .to_sprite(BitmapRef::from_table(gif.table.clone(), 0));
+
+        commands.commands().entity(entity).insert((sprite, animation));
+    }
+}
@@ -6,6 +6,7 @@ extern crate alloc;
 #[macro_use]
 extern crate playdate as pd;
 pub mod game;
+pub mod gif;
 pub mod rkyv;
 pub mod tiled;
 mod state;
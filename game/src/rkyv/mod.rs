@@ -1,19 +1,51 @@
-use alloc::vec::Vec;
-use bevy_playdate::jobs::{load_file_bytes, AsyncLoadCtx};
+use bevy_playdate::jobs::{load_file_bytes, AsyncLoadCtx, GenJobExtensions};
 use pd_asset::archive::{AlignVec, OwnedArchived};
+use pd_asset::block::Blocks;
+use pd_asset::header::{ArchiveKind, AssetHeader, AssetKind};
 use pd_asset::rkyv::api::high::HighValidator;
 use pd_asset::rkyv::bytecheck::CheckBytes;
 use pd_asset::rkyv::Portable;
 use pd_asset::RkyvError;
 
+/// Loads an archive written by the editor, checking its [`AssetHeader`] against `kind`
+/// before decompressing it.
+///
+/// The archive is decompressed one [block](pd_asset::block) at a time, straight into the aligned
+/// buffer, yielding after each so a big map doesn't blow the frame budget.
 pub async fn load_and_decompress(
     async_load_ctx: &mut AsyncLoadCtx,
     path: &str,
-) -> anyhow::Result<Vec<u8>> {
+    kind: AssetKind,
+) -> anyhow::Result<AlignVec> {
     let bytes = load_file_bytes(async_load_ctx, path).await?;
-    let bytes = lz4_flex::decompress_size_prepended(&bytes)?;
+    let header = AssetHeader::from_bytes(&bytes)
+        .and_then(|header| header.expect(kind).map(|_| header))
+        .map_err(|err| anyhow::anyhow!("{path}: {err}"))?;
 
-    Ok(bytes)
+    let len = header.uncompressed_len as usize;
+    let mut aligned = AlignVec::with_capacity(len);
+    aligned.resize(len, 0);
+
+    for block in Blocks::new(&bytes[AssetHeader::SIZE..], len) {
+        let block = block.map_err(|err| anyhow::anyhow!("{path}: {err}"))?;
+        let out = &mut aligned[block.out.clone()];
+        if block.stored {
+            out.copy_from_slice(block.data);
+        } else {
+            let n = lz4_flex::block::decompress_into(block.data, out)?;
+            if n != out.len() {
+                anyhow::bail!("{path}: block decompressed to {n} bytes, expected {}", out.len());
+            }
+        }
+
+        async_load_ctx.yield_next().await;
+    }
+
+    header
+        .verify(&aligned)
+        .map_err(|err| anyhow::anyhow!("{path}: {err}"))?;
+
+    Ok(aligned)
 }
 
 pub async fn load_compressed_archive<T>(
@@ -21,11 +53,9 @@ pub async fn load_compressed_archive<T>(
     path: &str,
 ) -> anyhow::Result<OwnedArchived<T>>
 where
-    T: Portable + for<'a> CheckBytes<HighValidator<'a, RkyvError>>,
+    T: ArchiveKind + Portable + for<'a> CheckBytes<HighValidator<'a, RkyvError>>,
 {
-    let bytes = load_and_decompress(async_load_ctx, path).await?;
-    let mut aligned = AlignVec::with_capacity(bytes.len());
-    aligned.extend_from_slice(&bytes);
+    let aligned = load_and_decompress(async_load_ctx, path, T::KIND).await?;
 
     Ok(OwnedArchived::new(aligned)?)
 }
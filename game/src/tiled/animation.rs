@@ -0,0 +1,93 @@
+use crate::tiled::Tile;
+use alloc::vec::Vec;
+use bevy_ecs::prelude::{Component, Query, Res};
+use bevy_playdate::asset::BitmapRef;
+use bevy_playdate::sprite::Sprite;
+use bevy_time::Time;
+use pd::sys::ffi::LCDBitmapFlip;
+use pd_asset::tileset::Frame;
+
+/// Animates the [`Sprite`] of a tile with the frames from its tileset.
+///
+/// The sprite must use a bitmap from the tileset's image table, i.e. be inserted by
+/// [`SpriteTableLoader`](super::SpriteTableLoader). All tiles with the same animation are kept in
+/// sync, the same as in Tiled.
+#[derive(Component, Clone, Debug)]
+pub struct TileAnimation {
+    frames: Vec<Frame>,
+    /// Sum of the duration of all frames, in milliseconds.
+    total_duration: u32,
+    flip: LCDBitmapFlip,
+    current: Option<usize>,
+}
+
+impl TileAnimation {
+    pub fn new(frames: Vec<Frame>, flip: LCDBitmapFlip) -> Option<Self> {
+        let total_duration = frames.iter().map(|frame| frame.duration).sum();
+        if total_duration == 0 {
+            return None;
+        }
+
+        Some(Self {
+            frames,
+            total_duration,
+            flip,
+            current: None,
+        })
+    }
+
+    /// Returns the animation of the tile, if it has one.
+    pub fn from_tile(tile: &Tile, flip: LCDBitmapFlip) -> Option<Self> {
+        let (data, _) = tile.data();
+        let frames = data.animation.as_ref()?;
+        let frames = frames
+            .iter()
+            .map(|frame| Frame {
+                tile_id: frame.tile_id.to_native(),
+                duration: frame.duration.to_native(),
+            })
+            .collect();
+
+        Self::new(frames, flip)
+    }
+
+    /// Returns the index of the frame shown `elapsed_ms` milliseconds after the start.
+    pub fn frame_at(&self, elapsed_ms: u64) -> usize {
+        let mut t = (elapsed_ms % self.total_duration as u64) as u32;
+        for (i, frame) in self.frames.iter().enumerate() {
+            if t < frame.duration {
+                return i;
+            }
+            t -= frame.duration;
+        }
+
+        self.frames.len() - 1
+    }
+
+    /// The tile id of the first frame.
+    pub fn first_tile_id(&self) -> u32 {
+        self.frames[0].tile_id
+    }
+}
+
+pub fn animate_tiles(time: Res<Time>, mut q_tiles: Query<(&mut TileAnimation, &mut Sprite)>) {
+    let elapsed_ms = time.elapsed().as_millis() as u64;
+
+    for (mut animation, mut sprite) in q_tiles.iter_mut() {
+        let frame = animation.frame_at(elapsed_ms);
+        if animation.current == Some(frame) {
+            continue;
+        }
+
+        let BitmapRef::Table(table, _) = sprite.bitmap() else {
+            continue;
+        };
+        let tile_id = animation.frames[frame].tile_id as usize;
+        if tile_id >= table.len() {
+            continue;
+        }
+
+        sprite.set_bitmap_ref(BitmapRef::from_table(table, tile_id), animation.flip);
+        animation.current = Some(frame);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::tiled::job::BatchCommands;
+use crate::tiled::{AssetLoader, SpriteLoader};
+use bevy_ecs::entity::Entity;
+use bevy_ecs::hierarchy::ChildOf;
+use bevy_ecs::prelude::{Component, Query, Res};
+use bevy_math::{BVec2, IVec2, Vec2};
+use bevy_platform::sync::Arc;
+use bevy_playdate::asset::{AssetAsync, BitmapAsset};
+use bevy_playdate::transform::{GlobalTransform, Transform};
+use bevy_playdate::view::{DrawOffset, Parallax, SCREEN_SIZE};
+use pd::api;
+use pd::graphics::bitmap::Bitmap;
+use pd::graphics::color::Color;
+use pd::sys::ffi::LCDBitmapFlip;
+use pd::sys::traits::AsRaw;
+
+/// An image layer repeated across the view. Must be the child of the layer entity.
+///
+/// The sprite is the image tiled enough times to cover the screen with a copy to spare on each
+/// side, and [`scroll_backdrops`] moves it by whole copies to keep the view covered.
+#[derive(Component, Clone, Debug)]
+pub struct Backdrop {
+    /// Size of a single copy of the image.
+    pub image_size: IVec2,
+    pub repeat: BVec2,
+}
+
+impl Backdrop {
+    /// Size of the bitmap drawn, in pixels.
+    pub fn size(&self) -> IVec2 {
+        let copies = SCREEN_SIZE / self.image_size.max(IVec2::ONE) + 3;
+        IVec2::select(self.repeat, self.image_size * copies, self.image_size)
+    }
+}
+
+/// Loads the image of a repeating image layer and inserts a sprite of it tiled to cover the
+/// view, with [`Backdrop`].
+pub struct BackdropLoader {
+    pub z_index: i16,
+    pub backdrop: Backdrop,
+}
+
+impl AssetLoader for BackdropLoader {
+    type Asset = BitmapAsset;
+
+    fn on_finish_load(
+        &self,
+        commands: &mut BatchCommands,
+        entity: Entity,
+        result: Result<Arc<Self::Asset>, <<Self as AssetLoader>::Asset as AssetAsync>::Error>,
+    ) {
+        let image = result.unwrap();
+        let size = self.backdrop.size();
+        let bitmap = Bitmap::new(size.x, size.y, Color::CLEAR).expect("create backdrop bitmap");
+        unsafe {
+            api!(graphics).pushContext.unwrap()(bitmap.as_raw());
+            api!(graphics).tileBitmap.unwrap()(
+                image.as_raw(),
+                0,
+                0,
+                size.x,
+                size.y,
+                LCDBitmapFlip::kBitmapUnflipped,
+            );
+            api!(graphics).popContext.unwrap()();
+        }
+
+        let sprite = SpriteLoader {
+            center: [0.0; 2],
+            z_index: self.z_index,
+            ignore_draw_offset: false,
+            flip: LCDBitmapFlip::kBitmapUnflipped,
+        }
+        .to_sprite(Arc::new(BitmapAsset(bitmap)).into());
+
+        commands
+            .commands()
+            .entity(entity)
+            .insert((sprite, self.backdrop.clone()));
+    }
+}
+
+/// Moves every [`Backdrop`] by whole copies of its image so it covers the view, taking the
+/// [`Parallax`] of its layer into account.
+pub fn scroll_backdrops(
+    mut q_backdrops: Query<(&Backdrop, &ChildOf, &mut Transform)>,
+    q_layers: Query<(&GlobalTransform, Option<&Parallax>)>,
+    offset: Res<DrawOffset>,
+) {
+    for (backdrop, child_of, mut transform) in q_backdrops.iter_mut() {
+        let Ok((layer_transform, parallax)) = q_layers.get(child_of.parent()) else {
+            continue;
+        };
+
+        // where the layer is drawn, not where it is
+        let layer_pos = layer_transform.0 + parallax.map_or(Vec2::ZERO, Parallax::shift);
+        let layer_pos = IVec2::new(layer_pos.x as i32, layer_pos.y as i32);
+        let image_size = backdrop.image_size.max(IVec2::ONE);
+        // one copy to spare before the top-left of the screen
+        let copies = (offset.top_left() - layer_pos).div_euclid(image_size) - 1;
+        let pos = IVec2::select(backdrop.repeat, copies * image_size, IVec2::ZERO).as_vec2();
+
+        if transform.0 != pos {
+            transform.0 = pos;
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::tiled::collision::TileLayerCollision;
+use crate::tiled::spawn::spawn_tile;
+use crate::tiled::{JobCommandsExt, LayerData, Map, SpriteLoader};
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use bevy_ecs::entity::Entity;
+use bevy_ecs::hierarchy::ChildOf;
+use bevy_ecs::name::Name;
+use bevy_ecs::prelude::{Commands, Component, Query, Res};
+use bevy_math::{IVec2, Vec2};
+use bevy_platform::sync::Arc;
+use bevy_playdate::transform::{GlobalTransform, Transform};
+use bevy_playdate::view::{DrawOffset, Parallax};
+use bevy_playdate::visibility::Visibility;
+use hashbrown::HashMap;
+use pd::sys::ffi::LCDBitmapFlip;
+use pd_asset::tilemap::ChunkData;
+
+/// How many chunks outside the screen are kept spawned in each direction, so chunks have time to
+/// load their image before they scroll into view.
+pub const CHUNK_MARGIN: i32 = 1;
+
+/// Spawns and despawns the chunks of an infinite tile layer as the view moves.
+///
+/// Inserted by [`spawn`](super::spawn::spawn) on the entity of each infinite tile layer. Only the
+/// chunks that overlap the screen (plus [`CHUNK_MARGIN`]) exist as child entities at any time.
+#[derive(Component)]
+pub struct ChunkedTileLayer {
+    map: Arc<Map>,
+    layer_id: u32,
+    /// Z index of the chunk images. Tiles drawn individually use the one above.
+    z_index: i16,
+    /// If true, don't spawn an entity for each tile in a chunk.
+    is_static: bool,
+    loaded: HashMap<(i32, i32), Entity>,
+}
+
+impl ChunkedTileLayer {
+    pub fn new(map: Arc<Map>, layer_id: u32, z_index: i16, is_static: bool) -> Self {
+        Self {
+            map,
+            layer_id,
+            z_index,
+            is_static,
+            loaded: HashMap::new(),
+        }
+    }
+
+    /// Returns the entity of the chunk at the given chunk position, if it is currently spawned.
+    pub fn chunk_at(&self, x: i32, y: i32) -> Option<Entity> {
+        self.loaded.get(&(x, y)).copied()
+    }
+}
+
+pub fn stream_chunks(
+    mut q_layers: Query<(Entity, &GlobalTransform, Option<&Parallax>, &mut ChunkedTileLayer)>,
+    offset: Res<DrawOffset>,
+    mut commands: Commands,
+) {
+    for (entity, transform, parallax, mut chunked) in q_layers.iter_mut() {
+        let chunked = &mut *chunked;
+        let Some(layer) = chunked.map.get_layer(chunked.layer_id) else {
+            continue;
+        };
+        let LayerData::InfiniteTileLayer(tile_layer) = layer.data() else {
+            continue;
+        };
+
+        let (tile_width, tile_height) = chunked.map.tile_size();
+        let chunk_size = IVec2::new(
+            (tile_width * ChunkData::WIDTH) as i32,
+            (tile_height * ChunkData::HEIGHT) as i32,
+        );
+        // where the layer is drawn, not where it is
+        let layer_pos = transform.0 + parallax.map_or(Vec2::ZERO, Parallax::shift);
+        let layer_pos = IVec2::new(layer_pos.x as i32, layer_pos.y as i32);
+        let min = (offset.top_left() - layer_pos).div_euclid(chunk_size) - CHUNK_MARGIN;
+        let max = (offset.bottom_right() - layer_pos).div_euclid(chunk_size) + CHUNK_MARGIN;
+        let in_view = |(x, y): (i32, i32)| min.x <= x && x <= max.x && min.y <= y && y <= max.y;
+
+        let to_despawn: Vec<(i32, i32)> = chunked
+            .loaded
+            .keys()
+            .copied()
+            .filter(|&pos| !in_view(pos))
+            .collect();
+        for pos in to_despawn {
+            let chunk_entity = chunked.loaded.remove(&pos).unwrap();
+            commands.entity(chunk_entity).despawn();
+        }
+
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                if chunked.loaded.contains_key(&(x, y)) {
+                    continue;
+                }
+                let Some(chunk) = tile_layer.get_chunk(x, y) else {
+                    continue;
+                };
+
+                let mut chunk_entity = commands.spawn((
+                    Name::new("Chunk"),
+                    ChildOf(entity),
+                    Transform::from_xy((x * chunk_size.x) as f32, (y * chunk_size.y) as f32),
+                    Visibility::Inherited,
+                ));
+
+                if let Some(collision) = chunk.collision.as_ref() {
+                    chunk_entity.insert(TileLayerCollision::from(collision));
+                }
+
+                if let Some(image) = chunk.image.as_ref() {
+                    chunk_entity.insert_loading_asset(
+                        SpriteLoader {
+                            center: [0.0; 2],
+                            z_index: chunked.z_index,
+                            ignore_draw_offset: false,
+                            flip: LCDBitmapFlip::kBitmapUnflipped,
+                        },
+                        10,
+                        image.to_string(),
+                    );
+                }
+
+                let baked = chunk.image.is_some();
+                let is_static = chunked.is_static;
+                let z_index = chunked.z_index + 1;
+                chunk_entity.with_children(|c| {
+                    for (i, tile) in chunk.tiles().enumerate() {
+                        let Some(tile) = tile else {
+                            continue;
+                        };
+
+                        let i = i as u32;
+                        let [x, y] = [i % ChunkData::WIDTH, i / ChunkData::WIDTH];
+                        let pos = [(x * tile_width) as f32, (y * tile_height) as f32];
+                        spawn_tile(c, &tile, pos, baked, is_static, z_index);
+                    }
+                });
+
+                chunked.loaded.insert((x, y), chunk_entity.id());
+            }
+        }
+    }
+}
@@ -6,9 +6,10 @@ use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use alloc::{format, vec};
 use bevy_ecs::reflect::{ReflectBundle, ReflectComponent, ReflectResource};
+use bevy_playdate::color::LcdColor;
 use bevy_reflect::std_traits::ReflectDefault;
 use bevy_reflect::{
-    ArrayInfo, EnumInfo, NamedField, PartialReflect, Reflect, ReflectRef, StructInfo, TupleInfo,
+    ArrayInfo, EnumInfo, FromReflect, NamedField, PartialReflect, Reflect, ReflectRef, StructInfo, TupleInfo,
     TupleStructInfo, TypeInfo, TypeRegistration, TypeRegistry, UnnamedField, VariantInfo,
 };
 use derive_more::Display;
@@ -528,10 +529,10 @@ fn value_to_json(value: &dyn PartialReflect) -> serde_json::Value {
         ("alloc::borrow::Cow<str>", _, ReflectRef::Opaque(v)) => {
             serde_json::json!(*v.try_downcast_ref::<Cow<str>>().unwrap())
         }
-        // ("bevy_color::color::Color", _, _) => {
-        //     let c = value.try_downcast_ref::<Color>().unwrap();
-        //     serde_json::json!(format!("#{:08x}", c.to_linear().as_u32()))
-        // }
+        ("bevy_playdate::color::LcdColor", _, _) => {
+            let [r, g, b, a] = LcdColor::from_reflect(value).unwrap_or_default().to_rgba();
+            serde_json::json!(format!("#{a:02x}{r:02x}{g:02x}{b:02x}"))
+        }
         (_, TypeInfo::Enum(info), ReflectRef::Enum(v)) => {
             if info.iter().all(|v| matches!(v, VariantInfo::Unit(_))) {
                 serde_json::json!(v.variant_name())
@@ -628,7 +629,7 @@ fn type_to_field(
         }
         "alloc::borrow::Cow<str>" | "alloc::string::String" | "char" => (FieldType::String, None),
 
-        "bevy_color::color::Color" => (FieldType::Color, None),
+        "bevy_color::color::Color" | "bevy_playdate::color::LcdColor" => (FieldType::Color, None),
         "game::tiled::export::PathField" => (FieldType::File, None),
         f if f.starts_with("bevy_asset::handle::Handle") => (FieldType::File, None),
         path => {
@@ -7,6 +7,7 @@ use bevy_ecs::entity::Entity;
 use bevy_ecs::prelude::ReflectComponent;
 use bevy_ecs::reflect::{ReflectBundle, ReflectResource};
 use bevy_reflect::prelude::ReflectDefault;
+use bevy_playdate::color::LcdColor;
 use bevy_reflect::{
     DynamicArray, DynamicEnum, DynamicStruct, DynamicTuple, DynamicTupleStruct, DynamicVariant,
     FromReflect, NamedField, PartialReflect, Reflect, ReflectMut, ReflectRef, TypeInfo,
@@ -50,9 +51,9 @@ impl DeserializedMapProperties<false> {
                         );
                     }
                 }
-                // ArchivedLayerData::Group(group) => {
-                //     to_process.extend(group.layers());
-                // }
+                ArchivedLayerData::GroupLayer(group) => {
+                    to_process.extend(group.layers.iter());
+                }
                 ArchivedLayerData::FiniteTileLayer(_) | 
                     ArchivedLayerData::ImageLayer(_) |
                     ArchivedLayerData::InfiniteTileLayer(_) => {}
@@ -295,9 +296,9 @@ impl DeserializedProperties {
             ("f32", PV::IntValue(i), _) => Ok(Box::new(i.to_native() as f32)),
             ("f64", PV::IntValue(i), _) => Ok(Box::new(i.to_native() as f64)),
 
-            // ("bevy_color::color::Color", PV::ColorValue(c), _) => {
-            //     Ok(Box::new(Color::srgba_u8(c.red, c.green, c.blue, c.alpha)))
-            // }
+            ("bevy_playdate::color::LcdColor", PV::ColorValue(c), _) => {
+                Ok(Box::new(LcdColor::from_rgba(c.red, c.green, c.blue, c.alpha)))
+            }
             ("alloc::string::String", PV::StringValue(s), _) => Ok(Box::new(s.to_string())),
             ("char", PV::StringValue(s), _) => Ok(Box::new(s.chars().next().unwrap())),
             ("alloc::string::String", PV::FileValue(s), _) => Ok(Box::new(s.to_string())),
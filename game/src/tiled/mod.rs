@@ -1,8 +1,10 @@
 use crate::rkyv::{load_compressed_archive};
 use crate::tiled::load::{DeserializedMapProperties, DeserializedProperties};
 use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
-use bevy_app::{App, Last, Plugin, Startup};
+use bevy_app::{App, Last, Plugin, PostUpdate, Startup, Update};
 use bevy_ecs::change_detection::ResMut;
 use bevy_ecs::entity::{Entities, Entity};
 use bevy_ecs::event::EventReader;
@@ -12,34 +14,62 @@ use bevy_ecs::system::{Res, RunSystemOnce};
 use bevy_ecs::world::{CommandQueue, EntityWorldMut};
 use bevy_platform::sync::Arc;
 use bevy_playdate::asset::{AssetAsync, BitmapAsset, BitmapRef, BitmapTableAsset, ResAssetCache};
+use bevy_playdate::bundle;
+use bevy_playdate::color::LcdColor;
 use bevy_playdate::file::{BufferedWriter, FileHandle};
 use bevy_playdate::jobs::{AsyncLoadCtx, FinishedJobs, GenJobExtensions, JobFinished, JobHandle, Jobs, JobsScheduler};
 use bevy_playdate::sprite::Sprite;
+use bevy_playdate::transform::TransformSystem;
+use bevy_math::{Rect, Vec2};
 use bevy_reflect::Reflect;
 use core::ops::Deref;
 use derive_more::Deref;
 use no_std_io2::io::Write;
 use pd::sys::ffi::LCDBitmapFlip;
-use pd_asset::tilemap::{ArchivedChunkData, ArchivedFiniteTileLayer, ArchivedImageLayer, ArchivedInfiniteTileLayer, ArchivedLayer, ArchivedLayerData, ArchivedObjectLayer, ArchivedTilemap};
+use pd_asset::tilemap::{ArchivedChunkData, ArchivedFiniteTileLayer, ArchivedOrientation, ChunkData, ArchivedGroupLayer, ArchivedImageLayer, ArchivedInfiniteTileLayer, ArchivedLayer, ArchivedLayerData, ArchivedObjectLayer, ArchivedTilemap};
 use pd_asset::tileset::{ArchivedTileData, ArchivedTileset};
 use pd_asset::archive::OwnedArchived;
+use pd_asset::bundle::BUNDLE_EXTENSION;
 
+pub mod animation;
+pub mod backdrop;
+pub mod chunk;
 pub mod collision;
 pub mod export;
 pub mod job;
 mod load;
 pub mod spawn;
+pub mod text;
+pub mod tile_object;
 mod types_json;
+pub mod world;
 
 pub struct TiledPlugin;
 
 impl Plugin for TiledPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, export_types);
+        app.add_systems(Startup, export_types)
+            .add_systems(
+                Update,
+                (
+                    chunk::stream_chunks,
+                    animation::animate_tiles,
+                    world::stream_world_maps,
+                    backdrop::scroll_backdrops,
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                spawn::update_map_bounds.after(TransformSystem::TransformPropagate),
+            );
         // app.add_systems(Last, load_sprite.after(Jobs::run_jobs_system));
         add_loader::<SpriteLoader>(app);
         add_loader::<MapLoader>(app);
         add_loader::<SpriteTableLoader>(app);
+        add_loader::<text::TextLoader>(app);
+        add_loader::<tile_object::TileObjectLoader>(app);
+        add_loader::<backdrop::BackdropLoader>(app);
+        add_loader::<world::WorldLoader>(app);
 
         app.register_type::<Static>()
             .register_type::<export::PathField>();
@@ -138,9 +168,95 @@ impl Map {
             .map(move |layer| Layer { map: self, layer })
     }
 
-    pub fn get_tile_data(&self, tile: TileData) -> (&ArchivedTileData, &DeserializedProperties) {
+    /// All layers in the map, including the ones nested inside group layers.
+    pub fn all_layers(&self) -> impl Iterator<Item = Layer> {
+        let mut to_visit = Vec::from_iter(self.layers());
+        to_visit.reverse();
+
+        core::iter::from_fn(move || {
+            let layer = to_visit.pop()?;
+            if let LayerData::GroupLayer(group) = layer.data() {
+                to_visit.extend(group.layers().rev());
+            }
+            Some(layer)
+        })
+    }
+
+    /// Finds a layer by id, including layers nested inside group layers.
+    pub fn get_layer(&self, id: u32) -> Option<Layer> {
+        self.all_layers().find(|layer| layer.id == id)
+    }
+
+    /// The size of a tile in pixels.
+    pub fn tile_size(&self) -> (u32, u32) {
+        let map = self.map.data.access();
+        (map.tile_width.to_native(), map.tile_height.to_native())
+    }
+
+    /// The size of the map in tiles. For infinite maps, only what Tiled reports, see
+    /// [`Map::bounds`] instead.
+    pub fn size_in_tiles(&self) -> (u32, u32) {
+        let map = self.map.data.access();
+        (map.width.to_native(), map.height.to_native())
+    }
+
+    pub fn is_infinite(&self) -> bool {
+        self.map.data.access().infinite
+    }
+
+    pub fn orientation(&self) -> ArchivedOrientation {
+        self.map.data.access().orientation
+    }
+
+    /// The background color of the map, if set in Tiled.
+    pub fn background_color(&self) -> Option<LcdColor> {
+        self.map
+            .data
+            .access()
+            .background_color
+            .as_ref()
+            .map(|c| LcdColor::from_rgba(c.red, c.green, c.blue, c.alpha))
+    }
+
+    /// The area covered by the tiles of the map in pixels, relative to the map.
+    ///
+    /// For infinite maps this is the area covered by the chunks of every tile layer, or `None` if
+    /// there are none.
+    pub fn bounds(&self) -> Option<Rect> {
+        let (tile_width, tile_height) = self.tile_size();
+        let tile_size = Vec2::new(tile_width as f32, tile_height as f32);
+        if !self.is_infinite() {
+            let (width, height) = self.size_in_tiles();
+            return Some(Rect::from_corners(
+                Vec2::ZERO,
+                Vec2::new(width as f32, height as f32) * tile_size,
+            ));
+        }
+
+        let chunk_size = Vec2::new(ChunkData::WIDTH as f32, ChunkData::HEIGHT as f32) * tile_size;
+        let mut bounds: Option<Rect> = None;
+        for layer in self.all_layers() {
+            let LayerData::InfiniteTileLayer(tiles) = layer.data() else {
+                continue;
+            };
+            for ((x, y), _) in tiles.chunk_data() {
+                let min = Vec2::new(x as f32, y as f32) * chunk_size;
+                let chunk = Rect::from_corners(min, min + chunk_size);
+                bounds = Some(bounds.map_or(chunk, |bounds| bounds.union(chunk)));
+            }
+        }
+
+        bounds
+    }
+
+    /// Looks up the tileset data of a tile, in either [`TileEncoding`](pd_asset::tilemap::TileEncoding).
+    pub fn get_tile_data(
+        &self,
+        tile: impl Into<TileData>,
+    ) -> (&ArchivedTileData, &DeserializedProperties) {
+        let tile = tile.into();
         let map = tile.get_tilemap_idx();
-        let tile_n = tile.tile_id;
+        let tile_n = tile.tile_id();
 
         let tileset = &self.tilesets[map as usize];
 
@@ -158,9 +274,9 @@ pub struct Layer<'map> {
     layer: &'map ArchivedLayer,
 }
 
-impl Layer<'_> {
-    pub fn data(&self) -> LayerData {
-        match &self.layer_data {
+impl<'map> Layer<'map> {
+    pub fn data(&self) -> LayerData<'map> {
+        match &self.layer.layer_data {
             ArchivedLayerData::FiniteTileLayer(layer) => LayerData::FiniteTileLayer(FiniteTileLayer {
                 map: self.map,
                 data: layer,
@@ -174,6 +290,10 @@ impl Layer<'_> {
                 data: layer,
             },
             ArchivedLayerData::ImageLayer(layer) => LayerData::ImageLayer(layer),
+            ArchivedLayerData::GroupLayer(layer) => LayerData::GroupLayer(GroupLayer {
+                map: self.map,
+                data: layer,
+            }),
         }
     }
 
@@ -205,6 +325,24 @@ pub enum LayerData<'map> {
         data: &'map ArchivedObjectLayer,
     },
     ImageLayer(&'map ArchivedImageLayer),
+    GroupLayer(GroupLayer<'map>),
+}
+
+#[derive(Deref)]
+pub struct GroupLayer<'map> {
+    map: &'map Map,
+    #[deref]
+    data: &'map ArchivedGroupLayer,
+}
+
+impl<'map> GroupLayer<'map> {
+    pub fn layers(&self) -> impl DoubleEndedIterator<Item = Layer<'map>> {
+        let map = self.map;
+        self.data
+            .layers
+            .iter()
+            .map(move |layer| Layer { map, layer })
+    }
 }
 
 #[derive(Deref)]
@@ -217,9 +355,9 @@ pub struct FiniteTileLayer<'map> {
 impl FiniteTileLayer<'_> {
     pub fn tiles(&self) -> impl Iterator<Item = Option<Tile>> {
         self.data.tiles.iter().map(|tile| {
-            tile.as_ref().map(|t| Tile {
+            tile.map(|tile| Tile {
                 map: self.map,
-                tile: *t,
+                tile,
             })
         })
     }
@@ -233,6 +371,15 @@ pub struct InfiniteTileLayer<'map> {
 }
 
 impl InfiniteTileLayer<'_> {
+    /// Gets the chunk at the given chunk position, see
+    /// [`ArchivedChunkData::tile_to_chunk_pos`] to convert from a tile position.
+    pub fn get_chunk(&self, x: i32, y: i32) -> Option<Chunk> {
+        self.data.get_chunk(x, y).map(|chunk| Chunk {
+            map: self.map,
+            chunk,
+        })
+    }
+
     pub fn chunks(&self) -> impl ExactSizeIterator<Item=((i32, i32), Chunk)> {
         self.data.chunk_data()
             .map(move |(pos, chunk)| (pos, Chunk {
@@ -249,7 +396,16 @@ pub struct Chunk<'map> {
     chunk: &'map ArchivedChunkData,
 }
 
-
+impl Chunk<'_> {
+    pub fn tiles(&self) -> impl Iterator<Item = Option<Tile>> {
+        self.chunk.tiles.iter().map(|tile| {
+            tile.map(|tile| Tile {
+                map: self.map,
+                tile,
+            })
+        })
+    }
+}
 
 #[derive(Deref)]
 pub struct ObjectLayer<'map> {
@@ -265,7 +421,7 @@ pub struct ObjectLayer<'map> {
 //     }
 // }
 
-pub use pd_asset::tilemap::Tile as TileData;
+pub use pd_asset::tilemap::WideTile as TileData;
 use crate::tiled::job::{BatchCommands};
 
 #[derive(Deref)]
@@ -285,6 +441,9 @@ impl AssetAsync for Map {
     type Error = anyhow::Error;
 
     async fn load(load_cx: &mut AsyncLoadCtx, path: &str) -> Result<Self, Self::Error> {
+        // if the editor bundled this level, read the map and its tilesets from the one file
+        let _bundle = bundle::mount_if_exists(&bundle_path(path));
+
         let map = load_cx.load_asset::<TiledMap>(path.into()).await?;
 
         let archived_map = map.data.access();
@@ -300,6 +459,13 @@ impl AssetAsync for Map {
     }
 }
 
+/// Path of the bundle the editor writes for the map at `path`, e.g.
+/// `assets/level-1.tmb` -> `assets/level-1.bundle`.
+fn bundle_path(path: &str) -> String {
+    let stem = path.rsplit_once('.').map_or(path, |(stem, _)| stem);
+    format!("{stem}.{BUNDLE_EXTENSION}")
+}
+
 fn export_types(reg: Res<AppTypeRegistry>) {
     let path = "type-export.json";
     let file = FileHandle::write_only(path, false).unwrap();
@@ -362,11 +528,12 @@ pub struct SpriteLoader {
     pub center: [f32; 2],
     pub z_index: i16,
     pub ignore_draw_offset: bool,
+    pub flip: LCDBitmapFlip,
 }
 
 impl SpriteLoader {
     pub fn to_sprite(&self, image: BitmapRef) -> Sprite {
-        let sprite = Sprite::new_from_bitmap(image, LCDBitmapFlip::kBitmapUnflipped);
+        let sprite = Sprite::new_from_bitmap(image, self.flip);
         sprite.set_center(self.center[0], self.center[1]);
         sprite.set_z_index(self.z_index);
         sprite.set_ignores_draw_offset(self.ignore_draw_offset);
@@ -381,6 +548,7 @@ impl Default for SpriteLoader {
             center: [0.5; 2],
             z_index: 0,
             ignore_draw_offset: false,
+            flip: LCDBitmapFlip::kBitmapUnflipped,
         }
     }
 }
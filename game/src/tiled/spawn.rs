@@ -1,18 +1,28 @@
+use crate::tiled::backdrop::{Backdrop, BackdropLoader};
+use crate::tiled::chunk::ChunkedTileLayer;
 use crate::tiled::collision::TileLayerCollision;
-use crate::tiled::{JobCommandsExt, LayerData, Map, SpriteLoader, SpriteTableLoader, Static};
+use crate::tiled::load::DeserializedProperties;
+use crate::tiled::animation::TileAnimation;
+use crate::tiled::{JobCommandsExt, Layer, LayerData, Map, Tile, SpriteLoader, SpriteTableLoader, Static};
 use alloc::string::ToString;
 use alloc::vec::Vec;
 use bevy_ecs::entity::Entity;
+use bevy_ecs::hierarchy::ChildSpawnerCommands;
 use bevy_ecs::name::Name;
-use bevy_ecs::prelude::{Component, EntityCommands, ReflectComponent};
+use bevy_ecs::prelude::{Added, Changed, Component, EntityCommands, Or, Query, ReflectComponent};
 use bevy_ecs::reflect::ReflectCommandExt;
+use bevy_math::{BVec2, IVec2, Rect, Vec2};
 use bevy_platform::sync::Arc;
 use bevy_reflect::Reflect;
-use bevy_playdate::transform::Transform;
+use bevy_playdate::transform::{GlobalTransform, Transform};
 use hashbrown::HashMap;
+use bevy_playdate::view::Parallax;
 use bevy_playdate::visibility::Visibility;
+use pd::sys::ffi::LCDBitmapFlip;
 use pd_asset::tilemap::ArchivedObjectShape;
 use crate::tiled::job::BatchCommands;
+use crate::tiled::text::{TextLoader, TextSprite};
+use crate::tiled::tile_object::{tile_flip, TileObjectLoader};
 
 /// Contains a reference to the map data.
 /// 
@@ -21,6 +31,40 @@ use crate::tiled::job::BatchCommands;
 #[derive(Component, Clone)]
 pub struct MapHandle(pub Arc<Map>);
 
+/// The area covered by the tiles of a map, see [`Map::bounds`].
+///
+/// Inserted on the map entity when it's spawned, if the map has tiles.
+#[derive(Component, Copy, Clone, PartialEq, Debug)]
+pub struct MapBounds {
+    /// Relative to the map entity.
+    pub local: Rect,
+    /// In world space, updated from the [`GlobalTransform`] of the map entity.
+    pub world: Rect,
+}
+
+impl MapBounds {
+    pub fn new(local: Rect) -> Self {
+        Self {
+            local,
+            world: local,
+        }
+    }
+}
+
+/// Moves the world space [`MapBounds`] of maps along with them.
+#[allow(clippy::type_complexity)]
+pub fn update_map_bounds(
+    mut q_maps: Query<
+        (&GlobalTransform, &mut MapBounds),
+        Or<(Changed<GlobalTransform>, Added<MapBounds>)>,
+    >,
+) {
+    for (transform, mut bounds) in q_maps.iter_mut() {
+        let local = bounds.local;
+        bounds.world = Rect::from_corners(local.min + transform.0, local.max + transform.0);
+    }
+}
+
 #[derive(Component, Clone)]
 pub struct TileLayer {
     _map: Arc<Map>,
@@ -37,12 +81,15 @@ pub fn spawn(entity_commands: &mut BatchCommands, entity: Entity, map: Arc<Map>)
     let mut commands = entity_commands.commands();
     let mut entity_commands = commands.entity(entity);
     entity_commands.insert(MapHandle(Arc::clone(&map)));
+    if let Some(bounds) = map.bounds() {
+        entity_commands.insert(MapBounds::new(bounds));
+    }
     // spawn all objects and create object-id-to-entity map
     let objects = {
         let mut objects: HashMap<u32, Entity> = HashMap::new();
         let mut entity_name: Vec<(Entity, _)> = Vec::new();
 
-        for layer in map.layers() {
+        for layer in map.all_layers() {
             if let LayerData::ObjectLayer { data, .. } = layer.data() {
                 for obj in data.objects.iter() {
                     let id = obj.id.to_native();
@@ -66,22 +113,55 @@ pub fn spawn(entity_commands: &mut BatchCommands, entity: Entity, map: Arc<Map>)
         objects
     };
 
-    let mut hydrated = map.map.properties.clone().hydrate(&objects);
+    let hydrated = map.map.properties.clone().hydrate(&objects);
     
     for component in hydrated.map.properties {
         entity_commands.insert_reflect(component);
     }
     
-    let mut z_index = 0;
+    let mut spawner = LayerSpawner {
+        map: &map,
+        objects: &objects,
+        layer_properties: hydrated.layers,
+        object_properties: hydrated.objects,
+        z_index: 0,
+        parallax: Vec2::ONE,
+    };
+
+    entity_commands.with_children(|commands| spawner.spawn_layers(commands, map.layers()));
+}
 
-    entity_commands.with_children(|commands| {
-        for layer in map.layers() {
+/// Spawns layer entities, recursing into group layers so the spawned hierarchy matches the one in
+/// Tiled.
+struct LayerSpawner<'a> {
+    map: &'a Arc<Map>,
+    objects: &'a HashMap<u32, Entity>,
+    layer_properties: HashMap<u32, DeserializedProperties>,
+    object_properties: HashMap<u32, DeserializedProperties>,
+    z_index: i16,
+    /// Parallax factor of the group layer being spawned, multiplied with the factors of the
+    /// layers in it the same as in Tiled.
+    parallax: Vec2,
+}
+
+impl LayerSpawner<'_> {
+    fn spawn_layers<'map>(
+        &mut self,
+        commands: &mut ChildSpawnerCommands,
+        layers: impl Iterator<Item = Layer<'map>>,
+    ) {
+        for layer in layers {
             let mut layer_entity = commands.spawn((
                 Name::new(layer.name.to_string()),
                 Transform::from_xy(layer.x.to_native(), layer.y.to_native()),
                 Visibility::inherited_or_hidden(layer.visible),
             ));
-            let reflect = hydrated.layers.remove(&layer.id.to_native()).unwrap();
+            let parallax =
+                self.parallax * Vec2::new(layer.parallax_x.to_native(), layer.parallax_y.to_native());
+            if parallax != Vec2::ONE {
+                layer_entity.insert(Parallax::new(parallax));
+            }
+            let reflect = self.layer_properties.remove(&layer.id.to_native()).unwrap();
 
             let is_static = reflect.properties.iter().any(|s| s.represents::<Static>());
 
@@ -95,92 +175,207 @@ pub fn spawn(entity_commands: &mut BatchCommands, entity: Entity, map: Arc<Map>)
                         layer_entity.insert(TileLayerCollision::from(collision));
                     }
                     
+                    let baked = tile_layer.image.is_some();
                     if let Some(image) = tile_layer.image.as_ref() {
-                        z_index += 1;
+                        self.z_index += 1;
                         layer_entity.insert_loading_asset(
                             SpriteLoader {
                                 center: [0.0; 2],
-                                z_index,
+                                z_index: self.z_index,
                                 ignore_draw_offset: false,
+                                flip: LCDBitmapFlip::kBitmapUnflipped,
                             },
                             10,
                             image.to_string(),
                         );
+                    }
 
-                        if is_static {
-                            continue;
-                        }
-
-                        // let width = tile_layer.width.to_native();
-                        layer_entity.with_children(|c| {
-                            for tile in tile_layer.tiles() {
-                                let Some(tile) = tile else {
-                                    continue;
-                                };
-
-                                // leaving this here for when i need to spawn other things
-                                // let i = i as u32;
-                                // let [x, y] = [i % width, i / width];
-                                // let [x, y] = [x * map_data.tile_width, y * map_data.tile_height];
-
-                                let mut tile_entity = c.spawn((Name::new("Tile"),));
+                    // individually drawn tiles go above the baked image
+                    self.z_index += 1;
+                    let z_index = self.z_index;
+                    let width = tile_layer.width.to_native();
+                    let (tile_width, tile_height) = self.map.tile_size();
+                    layer_entity.with_children(|c| {
+                        for (i, tile) in tile_layer.tiles().enumerate() {
+                            let Some(tile) = tile else {
+                                continue;
+                            };
 
-                                let (_, properties) = tile.data();
-                                for property in properties.properties.iter() {
-                                    tile_entity.insert_reflect(property.to_dynamic());
-                                }
-                            }
-                        });
-                    } else {
-                        todo!("implement individual tile drawing")
-                    }
+                            let i = i as u32;
+                            let [x, y] = [i % width, i / width];
+                            let pos = [(x * tile_width) as f32, (y * tile_height) as f32];
+                            spawn_tile(c, &tile, pos, baked, is_static, z_index);
+                        }
+                    });
                 }
                 LayerData::ObjectLayer { map, data } => {
                     for obj in data.objects.iter() {
                         // I could remove the object here,
                         // but it's all going to be dropped at once later.
-                        let entity = *objects.get(&obj.id.to_native()).unwrap();
+                        let entity = *self.objects.get(&obj.id.to_native()).unwrap();
                         layer_entity.add_child(entity);
                         let mut object = layer_entity.commands_mut().entity(entity);
 
-                        let reflect = hydrated.objects.remove(&obj.id.to_native()).unwrap();
+                        let reflect = self.object_properties.remove(&obj.id.to_native()).unwrap();
                         for property in reflect.properties {
                             object.insert_reflect(property);
                         }
 
-                        if let &ArchivedObjectShape::Tile(tile) = &obj.shape {
-                            let tileset = &map.tilesets[tile.get_tilemap_idx() as usize];
-                            let path = tileset.data.access().image_path.to_string();
+                        match &obj.shape {
+                            ArchivedObjectShape::Tile {
+                                tile,
+                                width,
+                                height,
+                            } => {
+                                let tileset = &map.tilesets[tile.get_tilemap_idx() as usize];
+                                let path = tileset.data.access().image_path.to_string();
+
+                                let tile = Tile { map, tile: *tile };
+                                let flip = tile_flip(&tile);
+                                if let Some(animation) = TileAnimation::from_tile(&tile, flip) {
+                                    object.insert(animation);
+                                }
 
-                            z_index += 1;
-                            object.insert_loading_asset(
-                                SpriteTableLoader {
+                                self.z_index += 1;
+                                object.insert_loading_asset(
+                                    TileObjectLoader {
+                                        z_index: self.z_index,
+                                        index: tile.tile_id() as usize,
+                                        flip,
+                                        size: [width.to_native(), height.to_native()],
+                                        rotation: obj.rotation.to_native(),
+                                    },
+                                    10,
+                                    path,
+                                );
+                            }
+                            ArchivedObjectShape::Text(text) => {
+                                self.z_index += 1;
+                                let loader = TextLoader {
                                     sprite_loader: SpriteLoader {
-                                        z_index,
-                                        ..SpriteLoader::default()
+                                        // text objects are positioned by their top-left corner
+                                        center: [0.0; 2],
+                                        z_index: self.z_index,
+                                        ignore_draw_offset: false,
+                                        flip: LCDBitmapFlip::kBitmapUnflipped,
                                     },
-                                    index: tile.tile_id as usize,
-                                },
-                                10,
-                                path,
-                            );
+                                    text: TextSprite::from(text),
+                                };
+
+                                match text.font.as_ref() {
+                                    Some(font) => {
+                                        object.insert_loading_asset(loader, 10, font.to_string());
+                                    }
+                                    None => {
+                                        object.insert(loader.to_sprite(None));
+                                    }
+                                }
+                            }
+                            _ => {}
                         }
                     }
                 }
+                LayerData::ImageLayer(image_layer)
+                    if image_layer.repeat_x || image_layer.repeat_y =>
+                {
+                    self.z_index += 1;
+                    let loader = BackdropLoader {
+                        z_index: self.z_index,
+                        backdrop: Backdrop {
+                            image_size: IVec2::new(
+                                image_layer.width.to_native(),
+                                image_layer.height.to_native(),
+                            ),
+                            repeat: BVec2::new(image_layer.repeat_x, image_layer.repeat_y),
+                        },
+                    };
+                    // the backdrop is moved around under the layer, which stays at its offset
+                    layer_entity.with_children(|c| {
+                        c.spawn((Name::new("Backdrop"), Transform::default(), Visibility::Inherited))
+                            .insert_loading_asset(loader, 10, image_layer.source.to_string());
+                    });
+                }
                 LayerData::ImageLayer(image_layer) => {
-                    z_index += 1;
+                    self.z_index += 1;
                     layer_entity.insert_loading_asset(
                         SpriteLoader {
                             center: [0.0; 2],
-                            z_index,
+                            z_index: self.z_index,
                             ignore_draw_offset: false,
+                            flip: LCDBitmapFlip::kBitmapUnflipped,
                         },
                         10,
                         image_layer.source.to_string(),
                     );
                 }
-                LayerData::InfiniteTileLayer(_) => {todo!("infinite tile layer")}
+                LayerData::GroupLayer(group) => {
+                    let outer = core::mem::replace(&mut self.parallax, parallax);
+                    layer_entity.with_children(|c| self.spawn_layers(c, group.layers()));
+                    self.parallax = outer;
+                }
+                LayerData::InfiniteTileLayer(_) => {
+                    // chunks are spawned around the view by `chunk::stream_chunks`
+                    self.z_index += 1;
+                    layer_entity.insert(ChunkedTileLayer::new(
+                        Arc::clone(self.map),
+                        layer.id.to_native(),
+                        self.z_index,
+                        is_static,
+                    ));
+                    // individually drawn tiles go above the chunk images
+                    self.z_index += 1;
+                }
             }
         }
-    });
+    }
+}
+
+/// Spawns the entity of a single tile of a tile layer or chunk at `pos`, relative to its parent.
+///
+/// If the tile isn't in the baked image (`baked` is false, or the tile is animated) it gets a
+/// sprite of its own. Tiles that are in the baked image are only spawned if the layer isn't
+/// [`Static`], so their properties can be queried.
+pub(crate) fn spawn_tile(
+    commands: &mut ChildSpawnerCommands,
+    tile: &Tile,
+    pos: [f32; 2],
+    baked: bool,
+    is_static: bool,
+    z_index: i16,
+) {
+    let flip = tile_flip(tile);
+    let animation = TileAnimation::from_tile(tile, flip);
+    let drawn = !baked || animation.is_some();
+    if is_static && !drawn {
+        return;
+    }
+
+    let mut tile_entity = commands.spawn((Name::new("Tile"), Transform::from_xy(pos[0], pos[1])));
+
+    if drawn {
+        let tileset = &tile.map.tilesets[tile.get_tilemap_idx() as usize];
+        tile_entity.insert_loading_asset(
+            SpriteTableLoader {
+                sprite_loader: SpriteLoader {
+                    center: [0.0; 2],
+                    z_index,
+                    ignore_draw_offset: false,
+                    flip,
+                },
+                index: tile.tile_id() as usize,
+            },
+            10,
+            tileset.data.access().image_path.to_string(),
+        );
+    }
+    if let Some(animation) = animation {
+        tile_entity.insert(animation);
+    }
+
+    if !is_static {
+        let (_, properties) = tile.data();
+        for property in properties.properties.iter() {
+            tile_entity.insert_reflect(property.to_dynamic());
+        }
+    }
 }
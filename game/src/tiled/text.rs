@@ -0,0 +1,171 @@
+use crate::tiled::job::BatchCommands;
+use crate::tiled::{AssetLoader, SpriteLoader};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use bevy_ecs::entity::Entity;
+use bevy_platform::sync::Arc;
+use bevy_playdate::asset::{AssetAsync, BitmapAsset, BitmapRef, FontAsset};
+use bevy_playdate::sprite::Sprite;
+use diagnostic::dbg;
+use pd::api;
+use pd::graphics::bitmap::Bitmap;
+use pd::graphics::color::Color;
+use pd::graphics::text::{draw_text, get_font_height, get_text_width, load_font, set_font, Font};
+use pd::sys::traits::AsRaw;
+use pd_asset::tilemap::{ArchivedHorizontalAlignment, ArchivedTextData, ArchivedVerticalAlignment};
+
+/// The font text objects without one of their own are drawn in.
+const SYSTEM_FONT_PATH: &str = "/System/Fonts/Asheville-Sans-14-Bold.pft";
+
+/// Owned copy of a text object, drawn into a bitmap once its font is loaded.
+#[derive(Clone, Debug)]
+pub struct TextSprite {
+    pub text: String,
+    pub pixel_size: u32,
+    pub wrap: bool,
+    pub halign: ArchivedHorizontalAlignment,
+    pub valign: ArchivedVerticalAlignment,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl From<&ArchivedTextData> for TextSprite {
+    fn from(value: &ArchivedTextData) -> Self {
+        Self {
+            text: value.text.to_string(),
+            pixel_size: value.pixel_size.to_native(),
+            wrap: value.wrap,
+            halign: value.halign,
+            valign: value.valign,
+            width: value.width.to_native(),
+            height: value.height.to_native(),
+        }
+    }
+}
+
+impl TextSprite {
+    /// Draws the text into a bitmap the size of the bounding box, using `font` or the system font
+    /// if `None`.
+    pub fn render(&self, font: Option<&Font>) -> Bitmap {
+        // the current font is whichever was set last, so the system font is set explicitly too
+        let system_font;
+        let font = match font {
+            Some(font) => font,
+            None => {
+                system_font = load_font(SYSTEM_FONT_PATH).expect("load system font");
+                &system_font
+            }
+        };
+
+        let width = (bevy_math::ops::ceil(self.width) as i32).max(1);
+        let height = (bevy_math::ops::ceil(self.height) as i32).max(1);
+        let bitmap = Bitmap::new(width, height, Color::CLEAR)
+            .expect("create text bitmap");
+
+        let line_height = get_font_height(font) as i32;
+        let lines = self.lines(font, width);
+        let text_height = line_height * lines.len() as i32;
+
+        let top = match self.valign {
+            ArchivedVerticalAlignment::Top => 0,
+            ArchivedVerticalAlignment::Center => (height - text_height) / 2,
+            ArchivedVerticalAlignment::Bottom => height - text_height,
+        };
+
+        unsafe {
+            api!(graphics).pushContext.unwrap()(bitmap.as_raw());
+        }
+        set_font(font);
+        for (i, line) in lines.iter().enumerate() {
+            let line_width = text_width(line, font);
+            let x = match self.halign {
+                ArchivedHorizontalAlignment::Left | ArchivedHorizontalAlignment::Justify => 0,
+                ArchivedHorizontalAlignment::Center => (width - line_width) / 2,
+                ArchivedHorizontalAlignment::Right => width - line_width,
+            };
+            if let Err(err) = draw_text(line, x, top + i as i32 * line_height) {
+                dbg!(err);
+            }
+        }
+        unsafe {
+            api!(graphics).popContext.unwrap()();
+        }
+
+        bitmap
+    }
+
+    /// Splits the text into lines on newlines and, if wrapping, on the last space that fits in
+    /// `width`. A single word wider than `width` gets a line of its own.
+    fn lines(&self, font: &Font, width: i32) -> Vec<&str> {
+        let mut out = Vec::new();
+        for paragraph in self.text.lines() {
+            if !self.wrap {
+                out.push(paragraph);
+                continue;
+            }
+
+            let mut rest = paragraph;
+            while !rest.is_empty() {
+                let mut end = rest.len();
+                while text_width(&rest[..end], font) > width {
+                    match rest[..end].rfind(' ') {
+                        Some(space) if space > 0 => end = space,
+                        _ => break,
+                    }
+                }
+                if end == rest.len() {
+                    out.push(rest);
+                    break;
+                }
+                // the word didn't fit anywhere, so break after it instead
+                if text_width(&rest[..end], font) > width {
+                    end = rest.find(' ').unwrap_or(rest.len());
+                }
+                out.push(&rest[..end]);
+                rest = rest[end..].trim_start_matches(' ');
+            }
+        }
+
+        out
+    }
+}
+
+fn text_width(text: &str, font: &Font) -> i32 {
+    get_text_width(text, Some(font), 0).unwrap_or(0)
+}
+
+/// Loads the font of a text object, then inserts a [`Sprite`] with
+/// the text drawn in it.
+pub struct TextLoader {
+    pub sprite_loader: SpriteLoader,
+    pub text: TextSprite,
+}
+
+impl TextLoader {
+    pub fn to_sprite(&self, font: Option<&Font>) -> Sprite {
+        let bitmap = Arc::new(BitmapAsset(self.text.render(font)));
+        self.sprite_loader.to_sprite(BitmapRef::from_bitmap(bitmap))
+    }
+}
+
+impl AssetLoader for TextLoader {
+    type Asset = FontAsset;
+
+    fn on_finish_load(
+        &self,
+        commands: &mut BatchCommands,
+        entity: Entity,
+        result: Result<Arc<Self::Asset>, <<Self as AssetLoader>::Asset as AssetAsync>::Error>,
+    ) {
+        match result {
+            Ok(font) => {
+                commands.commands().entity(entity).insert(self.to_sprite(Some(&font.0)));
+            }
+            Err(err) => {
+                // still show the text, just in the system font
+                dbg!(err);
+                commands.commands().entity(entity).insert(self.to_sprite(None));
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::tiled::job::BatchCommands;
+use crate::tiled::{AssetLoader, SpriteLoader, TileData};
+use bevy_ecs::entity::Entity;
+use bevy_math::ops;
+use bevy_platform::sync::Arc;
+use bevy_playdate::asset::{AssetAsync, BitmapAsset, BitmapRef, BitmapTableAsset};
+use pd::api;
+use pd::graphics::bitmap::Bitmap;
+use pd::graphics::color::Color;
+use pd::sys::ffi::LCDBitmapFlip;
+use pd::sys::traits::AsRaw;
+
+/// Loads the image table of a tile object's tileset, then inserts a sprite of the tile
+/// flipped, stretched to the object's size and rotated around its bottom-left corner, the same
+/// as in Tiled.
+///
+/// The sprite only uses the bitmap from the image table if the object isn't stretched or
+/// rotated, otherwise it gets a bitmap of its own, which
+/// [`TileAnimation`](super::animation::TileAnimation) leaves alone.
+pub struct TileObjectLoader {
+    pub z_index: i16,
+    /// Index of the tile in the image table.
+    pub index: usize,
+    pub flip: LCDBitmapFlip,
+    /// Size of the object in pixels.
+    pub size: [f32; 2],
+    /// Degrees, clockwise.
+    pub rotation: f32,
+}
+
+impl TileObjectLoader {
+    /// Draws `source` (`width` x `height` pixels) flipped, scaled and rotated into a new bitmap.
+    ///
+    /// Also returns the center to give the sprite so the object's position is at the bottom-left
+    /// corner of the (unrotated) tile.
+    fn render(&self, source: &Bitmap, width: i32, height: i32) -> (Bitmap, [f32; 2]) {
+        let [w, h] = self.size;
+        let (sin, cos) = ops::sin_cos(self.rotation.to_radians());
+        let out_width = (ops::ceil(ops::abs(w * cos) + ops::abs(h * sin)) as i32).max(1);
+        let out_height = (ops::ceil(ops::abs(w * sin) + ops::abs(h * cos)) as i32).max(1);
+
+        // drawRotatedBitmap can't flip, so flip into a copy first
+        let flipped = (self.flip != LCDBitmapFlip::kBitmapUnflipped).then(|| {
+            let flipped = Bitmap::new(width, height, Color::CLEAR).expect("create flipped tile bitmap");
+            unsafe {
+                api!(graphics).pushContext.unwrap()(flipped.as_raw());
+                api!(graphics).drawBitmap.unwrap()(source.as_raw(), 0, 0, self.flip);
+                api!(graphics).popContext.unwrap()();
+            }
+            flipped
+        });
+        let source = flipped.as_ref().unwrap_or(source);
+
+        let bitmap = Bitmap::new(out_width, out_height, Color::CLEAR)
+            .expect("create tile object bitmap");
+        unsafe {
+            api!(graphics).pushContext.unwrap()(bitmap.as_raw());
+            api!(graphics).drawRotatedBitmap.unwrap()(
+                source.as_raw(),
+                out_width / 2,
+                out_height / 2,
+                self.rotation,
+                0.5,
+                0.5,
+                w / width as f32,
+                h / height as f32,
+            );
+            api!(graphics).popContext.unwrap()();
+        }
+
+        // the middle of the tile, relative to the object's position
+        let (x, y) = (w / 2.0, -h / 2.0);
+        let middle = [x * cos - y * sin, x * sin + y * cos];
+        let center = [
+            (out_width / 2) as f32 - middle[0],
+            (out_height / 2) as f32 - middle[1],
+        ];
+
+        (bitmap, [center[0] / out_width as f32, center[1] / out_height as f32])
+    }
+}
+
+impl AssetLoader for TileObjectLoader {
+    type Asset = BitmapTableAsset;
+
+    fn on_finish_load(
+        &self,
+        commands: &mut BatchCommands,
+        entity: Entity,
+        result: Result<Arc<Self::Asset>, <<Self as AssetLoader>::Asset as AssetAsync>::Error>,
+    ) {
+        let table = result.unwrap();
+        let (width, height) = bitmap_size(&table[self.index]);
+
+        let sprite_loader = SpriteLoader {
+            // tile objects are positioned by their bottom-left corner
+            center: [0.0, 1.0],
+            z_index: self.z_index,
+            ignore_draw_offset: false,
+            flip: self.flip,
+        };
+        let sprite = if self.rotation == 0.0 && self.size == [width as f32, height as f32] {
+            sprite_loader.to_sprite(BitmapRef::from_table(table, self.index))
+        } else {
+            let (bitmap, center) = self.render(&table[self.index], width, height);
+            SpriteLoader {
+                center,
+                flip: LCDBitmapFlip::kBitmapUnflipped,
+                ..sprite_loader
+            }
+            .to_sprite(Arc::new(BitmapAsset(bitmap)).into())
+        };
+
+        commands.commands().entity(entity).insert(sprite);
+    }
+}
+
+/// The flip to draw a tile with. Tile objects can't be flipped diagonally, so that is ignored.
+pub fn tile_flip(tile: &TileData) -> LCDBitmapFlip {
+    match (tile.get_flip_x(), tile.get_flip_y()) {
+        (false, false) => LCDBitmapFlip::kBitmapUnflipped,
+        (true, false) => LCDBitmapFlip::kBitmapFlippedX,
+        (false, true) => LCDBitmapFlip::kBitmapFlippedY,
+        (true, true) => LCDBitmapFlip::kBitmapFlippedXY,
+    }
+}
+
+fn bitmap_size(bitmap: &Bitmap) -> (i32, i32) {
+    let (mut width, mut height, mut row_bytes) = (0, 0, 0);
+    let (mut mask, mut data) = (core::ptr::null_mut(), core::ptr::null_mut());
+    unsafe {
+        api!(graphics).getBitmapData.unwrap()(
+            bitmap.as_raw(),
+            &mut width,
+            &mut height,
+            &mut row_bytes,
+            &mut mask,
+            &mut data,
+        );
+    }
+
+    (width, height)
+}
@@ -0,0 +1,177 @@
+use crate::rkyv::load_compressed_archive;
+use crate::tiled::job::BatchCommands;
+use crate::tiled::{AssetLoader, JobCommandsExt, MapLoader};
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use bevy_ecs::entity::Entity;
+use bevy_ecs::hierarchy::ChildOf;
+use bevy_ecs::name::Name;
+use bevy_ecs::prelude::{Commands, Component, Query, Single, With};
+use bevy_math::IVec2;
+use bevy_platform::sync::Arc;
+use bevy_playdate::asset::AssetAsync;
+use bevy_playdate::jobs::AsyncLoadCtx;
+use bevy_playdate::transform::{GlobalTransform, Transform};
+use bevy_playdate::view::Camera;
+use diagnostic::dbg;
+use hashbrown::HashMap;
+use pd_asset::archive::OwnedArchived;
+use pd_asset::world::{ArchivedWorld, ArchivedWorldMap};
+
+/// How far apart (in pixels) two maps of a world can be and still count as neighbours.
+pub const WORLD_NEIGHBOUR_MARGIN: i32 = 16;
+
+pub struct WorldAsset {
+    pub data: OwnedArchived<ArchivedWorld>,
+}
+
+impl AssetAsync for WorldAsset {
+    type Error = anyhow::Error;
+
+    async fn load(load_cx: &mut AsyncLoadCtx, path: &str) -> Result<Self, Self::Error> {
+        let data = load_compressed_archive::<ArchivedWorld>(load_cx, path).await?;
+        Ok(Self { data })
+    }
+}
+
+/// Loads a world, then inserts [`WorldMaps`] to spawn its maps around the [`Camera`].
+pub struct WorldLoader;
+
+impl AssetLoader for WorldLoader {
+    type Asset = WorldAsset;
+
+    fn on_finish_load(
+        &self,
+        commands: &mut BatchCommands,
+        entity: Entity,
+        result: Result<Arc<Self::Asset>, <<Self as AssetLoader>::Asset as AssetAsync>::Error>,
+    ) {
+        match result {
+            Ok(world) => {
+                commands.commands().entity(entity).insert(WorldMaps::new(world));
+            }
+            Err(err) => {
+                dbg!(err);
+            }
+        }
+    }
+}
+
+/// Spawns the maps of a world as children of this entity, offset by their position in the world.
+///
+/// Only the map the [`Camera`] is in and its neighbours (see [`WORLD_NEIGHBOUR_MARGIN`]) are
+/// spawned. When the camera crosses into another map, maps that are no longer neighbours are
+/// despawned and the new neighbours are loaded with [`MapLoader`].
+#[derive(Component)]
+pub struct WorldMaps {
+    world: Arc<WorldAsset>,
+    /// Index of the map the camera was last in.
+    current: Option<usize>,
+    loaded: HashMap<usize, Entity>,
+}
+
+impl WorldMaps {
+    pub fn new(world: Arc<WorldAsset>) -> Self {
+        Self {
+            world,
+            current: None,
+            loaded: HashMap::new(),
+        }
+    }
+
+    /// Returns the entity of the map at `index` in the world, if it is currently spawned.
+    pub fn map_entity(&self, index: usize) -> Option<Entity> {
+        self.loaded.get(&index).copied()
+    }
+
+    /// Index of the map the camera was last in.
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+}
+
+/// Returns the index of the map containing `pos`, or if there is none, the closest map.
+fn map_at(maps: &[ArchivedWorldMap], pos: IVec2) -> Option<usize> {
+    let distance = |map: &ArchivedWorldMap| {
+        let [min_x, min_y, max_x, max_y] = map.rect();
+        let closest = pos.clamp(IVec2::new(min_x, min_y), IVec2::new(max_x, max_y));
+        (closest - pos).length_squared()
+    };
+
+    maps.iter()
+        .position(|map| map.contains(pos.x, pos.y))
+        .or_else(|| {
+            maps.iter()
+                .enumerate()
+                .min_by_key(|(_, map)| distance(map))
+                .map(|(i, _)| i)
+        })
+}
+
+pub fn stream_world_maps(
+    mut q_worlds: Query<(Entity, &GlobalTransform, &mut WorldMaps)>,
+    camera: Option<Single<&GlobalTransform, With<Camera>>>,
+    mut commands: Commands,
+) {
+    let Some(camera) = camera else {
+        return;
+    };
+
+    for (entity, transform, mut world_maps) in q_worlds.iter_mut() {
+        let world_maps = &mut *world_maps;
+        let maps = world_maps.world.data.access().maps.as_slice();
+
+        let camera_pos = IVec2::new(
+            (camera.x - transform.x) as i32,
+            (camera.y - transform.y) as i32,
+        );
+        // keep the current map while the camera is outside of every map, e.g. in a gap
+        let current = match world_maps.current {
+            Some(current) if !maps[current].contains(camera_pos.x, camera_pos.y) => {
+                maps.iter()
+                    .position(|map| map.contains(camera_pos.x, camera_pos.y))
+                    .unwrap_or(current)
+            }
+            Some(current) => current,
+            None => {
+                let Some(current) = map_at(maps, camera_pos) else {
+                    continue;
+                };
+                current
+            }
+        };
+        if world_maps.current == Some(current) {
+            continue;
+        }
+        world_maps.current = Some(current);
+
+        let near = |i: usize| i == current || maps[current].is_near(&maps[i], WORLD_NEIGHBOUR_MARGIN);
+
+        let to_despawn: Vec<usize> = world_maps
+            .loaded
+            .keys()
+            .copied()
+            .filter(|&i| !near(i))
+            .collect();
+        for i in to_despawn {
+            let map_entity = world_maps.loaded.remove(&i).unwrap();
+            commands.entity(map_entity).despawn();
+        }
+
+        for (i, map) in maps.iter().enumerate() {
+            if !near(i) || world_maps.loaded.contains_key(&i) {
+                continue;
+            }
+
+            let map_entity = commands
+                .spawn((
+                    Name::new(map.path.to_string()),
+                    ChildOf(entity),
+                    Transform::from_xy(map.x.to_native() as f32, map.y.to_native() as f32),
+                ))
+                .insert_loading_asset(MapLoader, 0, map.path.to_string())
+                .id();
+            world_maps.loaded.insert(i, map_entity);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields, Member, parse_macro_input};
+
+/// Implements `AddDependencies` for the archived type and `AddDependenciesMut` for the type
+/// itself, visiting every field marked with `#[dependency]`.
+///
+/// The field types must implement the traits too, which `String`, `Option`, `Vec`, `HashMap`
+/// (values only) and any type deriving this do.
+#[proc_macro_derive(AddDependencies, attributes(dependency))]
+pub fn derive_add_dependencies(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "AddDependencies can't be derived for generic types",
+        ));
+    }
+
+    let name = &input.ident;
+    let archived_name = format_ident!("Archived{}", name);
+
+    let (archived_body, body) = match &input.data {
+        Data::Struct(data) => {
+            let members = dependency_members(&data.fields)?;
+            (
+                quote! {
+                    #( ::pd_asset::dependencies::AddDependencies::add_dependencies(&self.#members, dependencies); )*
+                },
+                quote! {
+                    #( ::pd_asset::dependencies::AddDependenciesMut::add_dependencies_mut(&mut self.#members, dependencies); )*
+                },
+            )
+        }
+        Data::Enum(data) => {
+            let mut archived_arms = Vec::new();
+            let mut arms = Vec::new();
+            for variant in &data.variants {
+                let members = dependency_members(&variant.fields)?;
+                if members.is_empty() {
+                    continue;
+                }
+
+                let variant = &variant.ident;
+                let bindings: Vec<Ident> = (0..members.len())
+                    .map(|i| Ident::new(&format!("__dependency_{i}"), Span::call_site()))
+                    .collect();
+                archived_arms.push(quote! {
+                    Self::#variant { #( #members: #bindings, )* .. } => {
+                        #( ::pd_asset::dependencies::AddDependencies::add_dependencies(#bindings, dependencies); )*
+                    }
+                });
+                arms.push(quote! {
+                    Self::#variant { #( #members: #bindings, )* .. } => {
+                        #( ::pd_asset::dependencies::AddDependenciesMut::add_dependencies_mut(#bindings, dependencies); )*
+                    }
+                });
+            }
+
+            (
+                quote! {
+                    #[allow(unreachable_patterns)]
+                    match self {
+                        #( #archived_arms )*
+                        _ => {}
+                    }
+                },
+                quote! {
+                    #[allow(unreachable_patterns)]
+                    match self {
+                        #( #arms )*
+                        _ => {}
+                    }
+                },
+            )
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "AddDependencies can't be derived for unions",
+            ));
+        }
+    };
+
+    Ok(quote! {
+        impl ::pd_asset::dependencies::AddDependencies for #archived_name {
+            #[allow(unused_variables)]
+            fn add_dependencies<'a: 'b, 'b>(
+                &'a self,
+                dependencies: &mut ::pd_asset::dependencies::__private::HashSet<&'b str>,
+            ) {
+                #archived_body
+            }
+        }
+
+        impl ::pd_asset::dependencies::AddDependenciesMut for #name {
+            #[allow(unused_variables)]
+            fn add_dependencies_mut<'a: 'b, 'b>(
+                &'a mut self,
+                dependencies: &mut ::pd_asset::dependencies::__private::Vec<
+                    &'b mut ::pd_asset::dependencies::__private::String,
+                >,
+            ) {
+                #body
+            }
+        }
+    })
+}
+
+/// Returns the fields marked with `#[dependency]`.
+fn dependency_members(fields: &Fields) -> syn::Result<Vec<Member>> {
+    let mut members = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let mut is_dependency = false;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("dependency")) {
+            attr.meta.require_path_only()?;
+            is_dependency = true;
+        }
+
+        if is_dependency {
+            members.push(match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(i.into()),
+            });
+        }
+    }
+
+    Ok(members)
+}
@@ -0,0 +1,175 @@
+//! Framing of the compressed archive bytes that follow an [`AssetHeader`](crate::header::AssetHeader).
+//!
+//! The archive is split into blocks of [`BLOCK_SIZE`] bytes (the last one may be shorter), each
+//! compressed on its own with the LZ4 block format. Every block is written as a little endian
+//! `u32` length followed by that many bytes. If [`STORED_FLAG`] is set in the length, the block
+//! didn't compress and is stored as is.
+//!
+//! Blocks are independent, so the runtime can decompress one at a time and yield in between.
+
+use core::fmt::{Display, Formatter};
+use core::ops::Range;
+
+/// Uncompressed size of every block but the last.
+pub const BLOCK_SIZE: usize = 16 * 1024;
+
+/// Set in the length of a block that is stored uncompressed.
+pub const STORED_FLAG: u32 = 1 << 31;
+
+/// Length prefix of a block with `len` bytes of payload.
+pub fn block_prefix(len: usize, stored: bool) -> [u8; 4] {
+    let len = u32::try_from(len).expect("block larger than 2GiB");
+    assert_eq!(len & STORED_FLAG, 0, "block larger than 2GiB");
+
+    let prefix = if stored { len | STORED_FLAG } else { len };
+    prefix.to_le_bytes()
+}
+
+/// A block of the compressed bytes, and where it goes in the decompressed archive.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Block<'a> {
+    pub data: &'a [u8],
+    pub stored: bool,
+    /// Range of the decompressed archive this block decompresses to.
+    pub out: Range<usize>,
+}
+
+/// Iterates over the blocks of compressed bytes that decompress to `uncompressed_len` bytes.
+pub struct Blocks<'a> {
+    bytes: &'a [u8],
+    uncompressed_len: usize,
+    pos: usize,
+    out_pos: usize,
+}
+
+impl<'a> Blocks<'a> {
+    pub fn new(bytes: &'a [u8], uncompressed_len: usize) -> Self {
+        Self {
+            bytes,
+            uncompressed_len,
+            pos: 0,
+            out_pos: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for Blocks<'a> {
+    type Item = Result<Block<'a>, BlockError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.out_pos >= self.uncompressed_len {
+            return if self.pos == self.bytes.len() {
+                None
+            } else {
+                self.pos = self.bytes.len();
+                Some(Err(BlockError::TrailingBytes))
+            };
+        }
+
+        let Some(prefix) = self.bytes.get(self.pos..self.pos + 4) else {
+            self.out_pos = self.uncompressed_len;
+            self.pos = self.bytes.len();
+            return Some(Err(BlockError::Truncated));
+        };
+        let prefix = u32::from_le_bytes(prefix.try_into().unwrap());
+        let stored = prefix & STORED_FLAG != 0;
+        let len = (prefix & !STORED_FLAG) as usize;
+
+        let start = self.pos + 4;
+        let Some(data) = self.bytes.get(start..start + len) else {
+            self.out_pos = self.uncompressed_len;
+            self.pos = self.bytes.len();
+            return Some(Err(BlockError::Truncated));
+        };
+
+        let out_len = BLOCK_SIZE.min(self.uncompressed_len - self.out_pos);
+        if stored && len != out_len {
+            self.out_pos = self.uncompressed_len;
+            self.pos = self.bytes.len();
+            return Some(Err(BlockError::StoredLength {
+                expected: out_len,
+                found: len,
+            }));
+        }
+
+        let out = self.out_pos..self.out_pos + out_len;
+        self.pos = start + len;
+        self.out_pos = out.end;
+
+        Some(Ok(Block { data, stored, out }))
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BlockError {
+    /// The bytes ended in the middle of a block.
+    Truncated,
+    /// There are bytes after the last block.
+    TrailingBytes,
+    StoredLength { expected: usize, found: usize },
+}
+
+impl Display for BlockError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "compressed archive is truncated"),
+            Self::TrailingBytes => write!(f, "compressed archive has bytes after the last block"),
+            Self::StoredLength { expected, found } => write!(
+                f,
+                "stored block is {found} bytes long, expected {expected}"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for BlockError {}
+
+#[cfg(test)]
+mod test {
+    use crate::block::{block_prefix, Block, BlockError, Blocks, BLOCK_SIZE};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    pub fn stored_blocks() {
+        let archive: Vec<u8> = (0..BLOCK_SIZE + 10).map(|i| i as u8).collect();
+        let mut bytes = Vec::new();
+        for chunk in archive.chunks(BLOCK_SIZE) {
+            bytes.extend_from_slice(&block_prefix(chunk.len(), true));
+            bytes.extend_from_slice(chunk);
+        }
+
+        let blocks = Blocks::new(&bytes, archive.len())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            blocks,
+            vec![
+                Block {
+                    data: &archive[..BLOCK_SIZE],
+                    stored: true,
+                    out: 0..BLOCK_SIZE,
+                },
+                Block {
+                    data: &archive[BLOCK_SIZE..],
+                    stored: true,
+                    out: BLOCK_SIZE..BLOCK_SIZE + 10,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn truncated_blocks() {
+        let mut bytes = block_prefix(10, false).to_vec();
+        bytes.extend_from_slice(&[0; 5]);
+        let mut blocks = Blocks::new(&bytes, 20);
+        assert_eq!(blocks.next(), Some(Err(BlockError::Truncated)));
+        assert_eq!(blocks.next(), None);
+
+        let bytes = [block_prefix(2, true).as_slice(), &[1, 2], &[3]].concat();
+        let mut blocks = Blocks::new(&bytes, 2);
+        assert!(blocks.next().unwrap().is_ok());
+        assert_eq!(blocks.next(), Some(Err(BlockError::TrailingBytes)));
+    }
+}
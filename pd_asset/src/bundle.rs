@@ -0,0 +1,164 @@
+use crate::header::SCHEMA_VERSION;
+use alloc::string::String;
+use core::fmt::{Display, Formatter};
+use hashbrown::HashMap;
+use rkyv::{Archive, Deserialize, Serialize};
+
+/// Magic bytes at the start of every bundle.
+pub const BUNDLE_MAGIC: [u8; 4] = *b"PDBN";
+
+/// Extension of bundle files.
+pub const BUNDLE_EXTENSION: &str = "bundle";
+
+/// Fixed-size header at the start of a bundle, followed by the [`BundleIndex`] archive and then
+/// the payloads of the members, back to back.
+///
+/// Layout (little endian):
+///
+/// | bytes  | field                     |
+/// |--------|---------------------------|
+/// | 0..4   | [`BUNDLE_MAGIC`]          |
+/// | 4..6   | schema version            |
+/// | 6..8   | reserved (0)              |
+/// | 8..12  | length of the index       |
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct BundleHeader {
+    pub schema_version: u16,
+    /// Length of the [`BundleIndex`] archive right after the header.
+    pub index_len: u32,
+}
+
+impl BundleHeader {
+    pub const SIZE: usize = 12;
+
+    pub fn new(index_len: u32) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            index_len,
+        }
+    }
+
+    /// Offset of the first payload from the start of the bundle. [`BundleEntry::offset`] is
+    /// relative to this.
+    pub fn payload_start(&self) -> u64 {
+        Self::SIZE as u64 + self.index_len as u64
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut out = [0; Self::SIZE];
+        out[0..4].copy_from_slice(&BUNDLE_MAGIC);
+        out[4..6].copy_from_slice(&self.schema_version.to_le_bytes());
+        out[8..12].copy_from_slice(&self.index_len.to_le_bytes());
+        out
+    }
+
+    /// Reads the header from the start of `bytes`, checking the magic and schema version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BundleError> {
+        let Some(bytes) = bytes.get(..Self::SIZE) else {
+            return Err(BundleError::TooShort(bytes.len()));
+        };
+        if bytes[0..4] != BUNDLE_MAGIC {
+            return Err(BundleError::BadMagic);
+        }
+        let schema_version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if schema_version != SCHEMA_VERSION {
+            return Err(BundleError::SchemaMismatch {
+                built: schema_version,
+                expected: SCHEMA_VERSION,
+            });
+        }
+
+        Ok(Self {
+            schema_version,
+            index_len: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+        })
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BundleError {
+    TooShort(usize),
+    BadMagic,
+    SchemaMismatch { built: u16, expected: u16 },
+}
+
+impl Display for BundleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooShort(len) => write!(
+                f,
+                "bundle too short for header: {len} < {} bytes",
+                BundleHeader::SIZE
+            ),
+            Self::BadMagic => write!(f, "not a bundle (bad magic bytes)"),
+            Self::SchemaMismatch { built, expected } => write!(
+                f,
+                "bundle built with schema version {built}, expected {expected}; re-run the editor"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for BundleError {}
+
+/// Where each member of a bundle is, by the path it would be opened with if it weren't bundled.
+#[derive(Archive, Serialize, Deserialize, Debug, Default)]
+#[rkyv(derive(Debug))]
+pub struct BundleIndex {
+    pub entries: HashMap<String, BundleEntry>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Archive, Serialize, Deserialize, Debug)]
+#[rkyv(derive(Debug))]
+pub struct BundleEntry {
+    /// Offset of the payload from [`BundleHeader::payload_start`].
+    pub offset: u32,
+    /// Length of the payload in the bundle.
+    pub len: u32,
+    pub compression: Compression,
+    /// Length of the member once decompressed. The same as `len` for [`Compression::None`].
+    pub uncompressed_len: u32,
+}
+
+impl From<&ArchivedBundleEntry> for BundleEntry {
+    fn from(value: &ArchivedBundleEntry) -> Self {
+        Self {
+            offset: value.offset.to_native(),
+            len: value.len.to_native(),
+            compression: match value.compression {
+                ArchivedCompression::None => Compression::None,
+                ArchivedCompression::Lz4 => Compression::Lz4,
+            },
+            uncompressed_len: value.uncompressed_len.to_native(),
+        }
+    }
+}
+
+/// How the payload of a [`BundleEntry`] is stored.
+///
+/// Archives are already compressed, so they are stored as is.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Archive, Serialize, Deserialize)]
+#[rkyv(derive(Debug, Copy, Clone, Eq, PartialEq))]
+pub enum Compression {
+    None,
+    /// LZ4 block format, without a size prefix.
+    Lz4,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bundle::{BundleError, BundleHeader};
+
+    #[test]
+    pub fn bundle_header_roundtrip() {
+        let header = BundleHeader::new(1234);
+        let bytes = header.to_bytes();
+        assert_eq!(BundleHeader::from_bytes(&bytes), Ok(header));
+        assert_eq!(header.payload_start(), 1246);
+
+        let mut bad = bytes;
+        bad[0] = b'X';
+        assert_eq!(BundleHeader::from_bytes(&bad), Err(BundleError::BadMagic));
+        assert_eq!(BundleHeader::from_bytes(&bytes[..4]), Err(BundleError::TooShort(4)));
+    }
+}
@@ -1,9 +1,19 @@
+use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec::Vec;
-use hashbrown::HashSet;
+use hashbrown::{HashMap, HashSet};
+use rkyv::boxed::ArchivedBox;
+use rkyv::collections::swiss_table::ArchivedHashMap;
+use rkyv::option::ArchivedOption;
+use rkyv::string::ArchivedString;
+use rkyv::vec::ArchivedVec;
+
+/// Derives both traits, for the type and its archived counterpart, from the fields marked with
+/// `#[dependency]`.
+pub use pd_asset_macros::AddDependencies;
 
 // This is a trait mostly just so I don't mess up the lifetimes.
-// It also lets me implement it on `Properties` (type alias for `Hashmap`)
+// It also lets me implement it on containers like `HashMap`, which `Properties` is an alias for
 pub trait AddDependencies {
     fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>);
 }
@@ -11,3 +21,85 @@ pub trait AddDependencies {
 pub trait AddDependenciesMut {
     fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>);
 }
+
+/// Used by the code generated by the derive.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::string::String;
+    pub use alloc::vec::Vec;
+    pub use hashbrown::HashSet;
+}
+
+impl AddDependencies for ArchivedString {
+    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
+        dependencies.insert(self.as_str());
+    }
+}
+
+impl AddDependenciesMut for String {
+    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
+        dependencies.push(self);
+    }
+}
+
+impl<T: AddDependencies> AddDependencies for ArchivedOption<T> {
+    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
+        if let Some(value) = self.as_ref() {
+            value.add_dependencies(dependencies);
+        }
+    }
+}
+
+impl<T: AddDependenciesMut> AddDependenciesMut for Option<T> {
+    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
+        if let Some(value) = self {
+            value.add_dependencies_mut(dependencies);
+        }
+    }
+}
+
+impl<T: AddDependencies> AddDependencies for ArchivedVec<T> {
+    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
+        for value in self.iter() {
+            value.add_dependencies(dependencies);
+        }
+    }
+}
+
+impl<T: AddDependenciesMut> AddDependenciesMut for Vec<T> {
+    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
+        for value in self.iter_mut() {
+            value.add_dependencies_mut(dependencies);
+        }
+    }
+}
+
+impl<T: AddDependencies> AddDependencies for ArchivedBox<T> {
+    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
+        self.get().add_dependencies(dependencies);
+    }
+}
+
+impl<T: AddDependenciesMut> AddDependenciesMut for Box<T> {
+    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
+        (**self).add_dependencies_mut(dependencies);
+    }
+}
+
+/// Only the values are visited, keys are never dependencies.
+impl<K, V: AddDependencies, H> AddDependencies for ArchivedHashMap<K, V, H> {
+    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
+        for value in self.values() {
+            value.add_dependencies(dependencies);
+        }
+    }
+}
+
+/// Only the values are visited, keys are never dependencies.
+impl<K, V: AddDependenciesMut, S> AddDependenciesMut for HashMap<K, V, S> {
+    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
+        for value in self.values_mut() {
+            value.add_dependencies_mut(dependencies);
+        }
+    }
+}
@@ -1,9 +1,31 @@
 ﻿use alloc::string::String;
+use alloc::vec::Vec;
 use rkyv::{Archive, Deserialize, Serialize};
+use crate::dependencies::AddDependencies;
+use crate::header::{ArchiveKind, AssetKind};
 
-#[derive(Archive, Serialize, Deserialize, Debug)]
+#[derive(Archive, Serialize, Deserialize, Debug, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub struct Gif {
+    #[dependency]
     pub image_path: String,
+    /// Rate to play every frame of the image table at, if `frames` is empty.
     pub fps: f32,
-}
\ No newline at end of file
+    /// The frames to play in order, for animations with frames of different lengths or that
+    /// don't use the whole image table.
+    pub frames: Vec<GifFrame>,
+}
+
+/// A single frame of a [`Gif`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[rkyv(derive(Debug, Copy, Clone, Eq, PartialEq))]
+pub struct GifFrame {
+    /// Index of the bitmap to show in the image table.
+    pub index: u32,
+    /// How long the frame is shown for, in milliseconds.
+    pub duration: u32,
+}
+
+impl ArchiveKind for ArchivedGif {
+    const KIND: AssetKind = AssetKind::Gif;
+}
@@ -0,0 +1,255 @@
+use core::fmt::{Display, Formatter};
+
+/// Magic bytes at the start of every exported archive.
+pub const MAGIC: [u8; 4] = *b"PDAB";
+
+/// Version of the archived layout of every type in this crate.
+///
+/// Bump this whenever an archived type (or anything it contains) changes, or the
+/// [block framing](crate::block) of the compressed bytes does, so stale exports are
+/// rejected with a clear error instead of failing validation (or worse, passing it).
+pub const SCHEMA_VERSION: u16 = 12;
+
+/// What kind of asset an archive holds.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[repr(u8)]
+pub enum AssetKind {
+    Tilemap = 0,
+    Tileset = 1,
+    Gif = 2,
+    World = 3,
+}
+
+impl AssetKind {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Tilemap),
+            1 => Some(Self::Tileset),
+            2 => Some(Self::Gif),
+            3 => Some(Self::World),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tilemap => "tilemap",
+            Self::Tileset => "tileset",
+            Self::Gif => "gif",
+            Self::World => "world",
+        }
+    }
+}
+
+impl Display for AssetKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Implemented by the archived form of every asset that gets written with an [`AssetHeader`].
+pub trait ArchiveKind {
+    const KIND: AssetKind;
+}
+
+/// Fixed-size header written in front of the compressed archive bytes.
+///
+/// Layout (little endian):
+///
+/// | bytes  | field              |
+/// |--------|--------------------|
+/// | 0..4   | [`MAGIC`]          |
+/// | 4      | [`AssetKind`]      |
+/// | 5      | reserved (0)       |
+/// | 6..8   | schema version     |
+/// | 8..12  | uncompressed length|
+/// | 12..16 | checksum           |
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct AssetHeader {
+    pub kind: AssetKind,
+    pub schema_version: u16,
+    /// Length of the archive bytes after decompression.
+    pub uncompressed_len: u32,
+    /// [`checksum`] of the archive bytes after decompression.
+    pub checksum: u32,
+}
+
+impl AssetHeader {
+    pub const SIZE: usize = 16;
+
+    /// Creates a header for the given (uncompressed) archive bytes using the current
+    /// [`SCHEMA_VERSION`].
+    pub fn new(kind: AssetKind, archive: &[u8]) -> Self {
+        Self {
+            kind,
+            schema_version: SCHEMA_VERSION,
+            uncompressed_len: u32::try_from(archive.len()).expect("archive larger than 4GiB"),
+            checksum: checksum(archive),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut out = [0; Self::SIZE];
+        out[0..4].copy_from_slice(&MAGIC);
+        out[4] = self.kind as u8;
+        out[6..8].copy_from_slice(&self.schema_version.to_le_bytes());
+        out[8..12].copy_from_slice(&self.uncompressed_len.to_le_bytes());
+        out[12..16].copy_from_slice(&self.checksum.to_le_bytes());
+        out
+    }
+
+    /// Reads the header from the start of `bytes`. Does not check the kind or schema version,
+    /// use [`AssetHeader::expect`] for that.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, HeaderError> {
+        let Some(bytes) = bytes.get(..Self::SIZE) else {
+            return Err(HeaderError::TooShort(bytes.len()));
+        };
+        if bytes[0..4] != MAGIC {
+            return Err(HeaderError::BadMagic);
+        }
+        let kind = AssetKind::from_u8(bytes[4]).ok_or(HeaderError::UnknownKind(bytes[4]))?;
+        let [a, b, c, d, e, f, g, h, i, j] = bytes[6..16].try_into().unwrap();
+
+        Ok(Self {
+            kind,
+            schema_version: u16::from_le_bytes([a, b]),
+            uncompressed_len: u32::from_le_bytes([c, d, e, f]),
+            checksum: u32::from_le_bytes([g, h, i, j]),
+        })
+    }
+
+    /// Checks that this header describes an asset of the given kind, built with the
+    /// [`SCHEMA_VERSION`] this crate was compiled with.
+    pub fn expect(&self, kind: AssetKind) -> Result<(), HeaderError> {
+        if self.kind != kind {
+            return Err(HeaderError::WrongKind {
+                expected: kind,
+                found: self.kind,
+            });
+        }
+        if self.schema_version != SCHEMA_VERSION {
+            return Err(HeaderError::SchemaMismatch {
+                built: self.schema_version,
+                expected: SCHEMA_VERSION,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks the decompressed archive bytes against the length and checksum in this header.
+    pub fn verify(&self, archive: &[u8]) -> Result<(), HeaderError> {
+        if archive.len() != self.uncompressed_len as usize {
+            return Err(HeaderError::LengthMismatch {
+                expected: self.uncompressed_len as usize,
+                found: archive.len(),
+            });
+        }
+        let found = checksum(archive);
+        if found != self.checksum {
+            return Err(HeaderError::ChecksumMismatch {
+                expected: self.checksum,
+                found,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum HeaderError {
+    TooShort(usize),
+    BadMagic,
+    UnknownKind(u8),
+    WrongKind { expected: AssetKind, found: AssetKind },
+    SchemaMismatch { built: u16, expected: u16 },
+    LengthMismatch { expected: usize, found: usize },
+    ChecksumMismatch { expected: u32, found: u32 },
+}
+
+impl Display for HeaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooShort(len) => write!(f, "file is too short for an asset header ({len} bytes)"),
+            Self::BadMagic => write!(f, "missing asset header, re-run the editor to export it"),
+            Self::UnknownKind(kind) => write!(f, "unknown asset kind {kind}"),
+            Self::WrongKind { expected, found } => {
+                write!(f, "expected a {expected} archive, found a {found} archive")
+            }
+            Self::SchemaMismatch { built, expected } => write!(
+                f,
+                "asset built with schema v{built}, runtime expects v{expected}; re-run the editor"
+            ),
+            Self::LengthMismatch { expected, found } => write!(
+                f,
+                "decompressed to {found} bytes, header says {expected} bytes"
+            ),
+            Self::ChecksumMismatch { expected, found } => write!(
+                f,
+                "checksum mismatch: expected {expected:#010x}, found {found:#010x}"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for HeaderError {}
+
+/// Adler-32 checksum of `bytes`.
+pub fn checksum(bytes: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    // largest n such that 255 * n * (n + 1) / 2 + (n + 1) * (MOD - 1) fits in a u32
+    const CHUNK: usize = 5552;
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in bytes.chunks(CHUNK) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod test {
+    use crate::header::{checksum, AssetHeader, AssetKind, HeaderError, SCHEMA_VERSION};
+
+    #[test]
+    pub fn header_roundtrip() {
+        let header = AssetHeader::new(AssetKind::Tileset, b"some archive bytes");
+        let bytes = header.to_bytes();
+        assert_eq!(AssetHeader::from_bytes(&bytes), Ok(header));
+        assert_eq!(header.expect(AssetKind::Tileset), Ok(()));
+        assert_eq!(header.verify(b"some archive bytes"), Ok(()));
+    }
+
+    #[test]
+    pub fn header_rejects_mismatch() {
+        let mut header = AssetHeader::new(AssetKind::Tilemap, b"abc");
+        assert!(matches!(
+            header.expect(AssetKind::Tileset),
+            Err(HeaderError::WrongKind { .. })
+        ));
+        assert!(matches!(
+            header.verify(b"abd"),
+            Err(HeaderError::ChecksumMismatch { .. })
+        ));
+
+        header.schema_version = SCHEMA_VERSION + 1;
+        assert_eq!(
+            header.expect(AssetKind::Tilemap),
+            Err(HeaderError::SchemaMismatch {
+                built: SCHEMA_VERSION + 1,
+                expected: SCHEMA_VERSION
+            })
+        );
+        assert_eq!(AssetHeader::from_bytes(b"PDA"), Err(HeaderError::TooShort(3)));
+    }
+
+    #[test]
+    pub fn adler32() {
+        assert_eq!(checksum(b"Wikipedia"), 0x11E6_0398);
+    }
+}
@@ -1,12 +1,18 @@
 #![no_std]
 extern crate alloc;
+// lets the code generated by `#[derive(AddDependencies)]` name this crate from inside it
+extern crate self as pd_asset;
 
 pub mod dependencies;
 pub mod properties;
 pub mod tilemap;
 pub mod tileset;
 pub mod gif;
+pub mod world;
 pub mod archive;
+pub mod header;
+pub mod block;
+pub mod bundle;
 
 pub use dependencies::AddDependencies;
 
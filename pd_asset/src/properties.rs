@@ -1,8 +1,7 @@
-use crate::dependencies::{AddDependencies, AddDependenciesMut};
+use crate::dependencies::AddDependencies;
 use alloc::string::String;
-use alloc::vec::Vec;
 use core::fmt::Debug;
-use hashbrown::{HashMap, HashSet};
+use hashbrown::HashMap;
 use rkyv::collections::swiss_table::ArchivedHashMap;
 use rkyv::string::ArchivedString;
 use rkyv::{Archive, Deserialize, Serialize};
@@ -10,7 +9,7 @@ use rkyv::{Archive, Deserialize, Serialize};
 /// Represents a custom property's value.
 ///
 /// Also read the [TMX docs](https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#tmx-properties).
-#[derive(Debug, PartialEq, Clone, Archive, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Clone, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(serialize_bounds(__S: rkyv::ser::Writer + rkyv::ser::Allocator, __S::Error: rkyv::rancor::Source))]
 #[rkyv(deserialize_bounds())]
 #[rkyv(bytecheck(bounds(__C: rkyv::validation::ArchiveContext)))]
@@ -22,13 +21,13 @@ pub enum PropertyValue {
     FloatValue(f32),
     /// A signed integer value. Corresponds to the `int` property type.
     IntValue(i32),
-    // /// A color value. Corresponds to the `color` property type.
-    // ColorValue(Color),
+    /// A color value. Corresponds to the `color` property type.
+    ColorValue(Color),
     /// A string value. Corresponds to the `string` property type.
     StringValue(String),
     /// A filepath value. Corresponds to the `file` property type.
     /// Holds the path relative to the map or tileset.
-    FileValue(String),
+    FileValue(#[dependency] String),
     /// An object ID value. Corresponds to the `object` property type.
     /// Holds the id of a referenced object, or 0 if unset.
     ObjectValue(u32),
@@ -39,48 +38,19 @@ pub enum PropertyValue {
         property_type: String,
         /// A set of properties.
         #[rkyv(omit_bounds)]
+        #[dependency]
         properties: Properties,
     },
 }
 
-impl AddDependencies for ArchivedPropertyValue {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        match self {
-            ArchivedPropertyValue::FileValue(file) => {
-                dependencies.insert(file);
-            }
-            ArchivedPropertyValue::ClassValue { properties, .. } => {
-                properties.add_dependencies(dependencies)
-            }
-            _ => {}
-        }
-    }
-}
-
-impl AddDependenciesMut for PropertyValue {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        match self {
-            Self::FileValue(file) => dependencies.push(file),
-            Self::ClassValue { properties, .. } => properties.add_dependencies_mut(dependencies),
-            _ => {}
-        }
-    }
-}
-
-impl AddDependencies for ArchivedProperties {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        for property in self.values() {
-            property.add_dependencies(dependencies);
-        }
-    }
-}
-
-impl AddDependenciesMut for Properties {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        for property in self.values_mut() {
-            property.add_dependencies_mut(dependencies);
-        }
-    }
+/// An 8-bit RGBA color, as stored in a `color` property.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Archive, Deserialize, Serialize)]
+#[rkyv(derive(Debug, PartialEq, Eq, Copy, Clone))]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
 }
 
 /// A custom property container.
@@ -89,7 +59,11 @@ pub type ArchivedProperties = ArchivedHashMap<ArchivedString, ArchivedPropertyVa
 
 #[cfg(test)]
 mod test {
-    use crate::properties::{ArchivedPropertyValue, PropertyValue};
+    use crate::dependencies::{AddDependencies, AddDependenciesMut};
+    use crate::properties::{ArchivedProperties, ArchivedPropertyValue, Color, Properties, PropertyValue};
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use hashbrown::HashSet;
     use rkyv::access;
     use rkyv::rancor::Error;
 
@@ -103,4 +77,54 @@ mod test {
             _ => panic!(),
         }
     }
+
+    #[test]
+    pub fn test_serialize_color() {
+        let color = Color {
+            red: 10,
+            green: 20,
+            blue: 30,
+            alpha: 255,
+        };
+        let buf = rkyv::to_bytes::<Error>(&PropertyValue::ColorValue(color)).unwrap();
+        let deserialized = access::<ArchivedPropertyValue, Error>(&buf).unwrap();
+        match deserialized {
+            ArchivedPropertyValue::ColorValue(c) => {
+                assert_eq!((c.red, c.green, c.blue, c.alpha), (10, 20, 30, 255))
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    pub fn test_derived_dependencies() {
+        let mut inner = Properties::new();
+        inner.insert("sprite".to_string(), PropertyValue::FileValue("player.png".to_string()));
+        inner.insert("name".to_string(), PropertyValue::StringValue("not-a-file".to_string()));
+        let mut properties = Properties::new();
+        properties.insert(
+            "class".to_string(),
+            PropertyValue::ClassValue {
+                property_type: "Actor".to_string(),
+                properties: inner,
+            },
+        );
+        properties.insert("sound".to_string(), PropertyValue::FileValue("jump.wav".to_string()));
+
+        let buf = rkyv::to_bytes::<Error>(&properties).unwrap();
+        let archived = access::<ArchivedProperties, Error>(&buf).unwrap();
+        let mut dependencies = HashSet::new();
+        archived.add_dependencies(&mut dependencies);
+        assert_eq!(dependencies, HashSet::from(["player.png", "jump.wav"]));
+
+        let mut dependencies = Vec::new();
+        properties.add_dependencies_mut(&mut dependencies);
+        assert_eq!(dependencies.len(), 2);
+        for dependency in dependencies {
+            dependency.insert_str(0, "assets/");
+        }
+        let mut dependencies = Vec::new();
+        properties.add_dependencies_mut(&mut dependencies);
+        assert!(dependencies.iter().all(|dependency| dependency.starts_with("assets/")));
+    }
 }
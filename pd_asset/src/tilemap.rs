@@ -1,257 +1,270 @@
-﻿use alloc::boxed::Box;
-use crate::dependencies::{AddDependencies, AddDependenciesMut};
-use crate::properties::Properties;
+﻿use crate::dependencies::AddDependencies;
+use crate::header::{ArchiveKind, AssetKind};
+use crate::properties::{Color, Properties};
 use alloc::string::String;
 use alloc::vec::Vec;
 use bytecheck::CheckBytes;
+use core::fmt::{Display, Formatter};
 use core::num::NonZeroU8;
-use core::ops::Deref;
-use hashbrown::{HashMap, HashSet};
+use hashbrown::HashMap;
 use rkyv::{Archive, Deserialize, Portable, Serialize};
 use rkyv::option::ArchivedOption;
 use rkyv::primitive::ArchivedI32;
 use rkyv::tuple::ArchivedTuple2;
 
-#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub struct Tilemap {
+    #[dependency]
     pub tilesets: Vec<String>,
+    #[dependency]
     pub layers: Vec<Layer>,
+    #[dependency]
     pub properties: Properties,
     pub tile_width: u32,
     pub tile_height: u32,
+    /// Width of the map in tiles. For infinite maps, only what Tiled reports.
+    pub width: u32,
+    /// Height of the map in tiles. For infinite maps, only what Tiled reports.
+    pub height: u32,
+    /// Whether the tile layers are [`InfiniteTileLayer`]s.
+    pub infinite: bool,
+    pub orientation: Orientation,
+    /// The color the map is cleared to, if set in Tiled.
+    pub background_color: Option<Color>,
+    /// Encoding of the tiles of every tile layer in the map.
+    pub tile_encoding: TileEncoding,
 }
 
-impl AddDependencies for ArchivedTilemap {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        dependencies.extend(self.tilesets.iter().map(|s| s.as_str()));
-        for layer in self.layers.iter() {
-            layer.add_dependencies(dependencies);
+impl Tilemap {
+    /// Re-encodes the tiles of every tile layer (including nested and infinite ones).
+    ///
+    /// If a tile doesn't fit in `encoding`, nothing is changed and that tile is returned.
+    pub fn set_tile_encoding(&mut self, encoding: TileEncoding) -> Result<(), WideTile> {
+        let mut storages = Vec::new();
+        let mut to_visit: Vec<&mut Layer> = self.layers.iter_mut().collect();
+        while let Some(layer) = to_visit.pop() {
+            match &mut layer.layer_data {
+                LayerData::FiniteTileLayer(layer) => storages.push(&mut layer.tiles),
+                LayerData::InfiniteTileLayer(layer) => {
+                    storages.extend(layer.chunks.values_mut().map(|chunk| &mut chunk.tiles))
+                }
+                LayerData::GroupLayer(group) => to_visit.extend(group.layers.iter_mut()),
+                LayerData::ObjectLayer(_) | LayerData::ImageLayer(_) => {}
+            }
         }
-        self.properties.add_dependencies(dependencies);
-    }
-}
 
-impl AddDependenciesMut for Tilemap {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        dependencies.extend(self.tilesets.iter_mut());
-        for layer in self.layers.iter_mut() {
-            layer.add_dependencies_mut(dependencies);
+        let converted = storages
+            .iter()
+            .map(|storage| storage.to_encoding(encoding))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (storage, converted) in storages.into_iter().zip(converted) {
+            *storage = converted;
         }
-        self.properties.add_dependencies_mut(dependencies);
+        self.tile_encoding = encoding;
+
+        Ok(())
     }
 }
 
-#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+impl ArchiveKind for ArchivedTilemap {
+    const KIND: AssetKind = AssetKind::Tilemap;
+}
+
+/// How the tiles of a map are laid out, the same as in Tiled.
+///
+/// Only [`Orthogonal`](Self::Orthogonal) maps are drawn correctly at runtime.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Archive, Deserialize, Serialize)]
+#[rkyv(derive(Debug, Copy, Clone, Eq, PartialEq))]
+pub enum Orientation {
+    #[default]
+    Orthogonal,
+    Isometric,
+    Staggered,
+    Hexagonal,
+}
+
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub struct Layer {
     pub name: String,
     pub id: u32,
     pub x: f32,
     pub y: f32,
+    /// How fast the layer scrolls relative to the camera on the x-axis, 1 being the same speed.
+    pub parallax_x: f32,
+    /// How fast the layer scrolls relative to the camera on the y-axis, 1 being the same speed.
+    pub parallax_y: f32,
+    /// Opacity in `0..=1`. Already applied to baked images and to the images of image layers.
+    pub opacity: f32,
+    /// Color multiplied with the tiles and image of the layer. Already applied like `opacity`.
+    pub tint_color: Option<Color>,
     pub visible: bool,
+    #[dependency]
     pub layer_data: LayerData,
+    #[dependency]
     pub properties: Properties,
 }
 
-impl AddDependencies for ArchivedLayer {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        self.layer_data.add_dependencies(dependencies);
-        self.properties.add_dependencies(dependencies);
-    }
-}
-
-impl AddDependenciesMut for Layer {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        self.layer_data.add_dependencies_mut(dependencies);
-        self.properties.add_dependencies_mut(dependencies);
-    }
-}
-
-#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub enum LayerData {
-    FiniteTileLayer(FiniteTileLayer),
-    InfiniteTileLayer(InfiniteTileLayer),
-    ObjectLayer(ObjectLayer),
-    ImageLayer(ImageLayer),
-    // Group Layer
-}
-
-impl AddDependencies for ArchivedLayerData {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        match self {
-            Self::FiniteTileLayer(layer) => layer.add_dependencies(dependencies),
-            Self::ObjectLayer(layer) => layer.add_dependencies(dependencies),
-            Self::ImageLayer(layer) => layer.add_dependencies(dependencies),
-            Self::InfiniteTileLayer(layer) => layer.add_dependencies(dependencies),
-        }
-    }
-}
-
-impl AddDependenciesMut for LayerData {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        match self {
-            Self::FiniteTileLayer(layer) => layer.add_dependencies_mut(dependencies),
-            Self::ObjectLayer(layer) => layer.add_dependencies_mut(dependencies),
-            Self::ImageLayer(layer) => layer.add_dependencies_mut(dependencies),
-            Self::InfiniteTileLayer(layer) => layer.add_dependencies_mut(dependencies),
-        }
-    }
+    FiniteTileLayer(#[dependency] FiniteTileLayer),
+    InfiniteTileLayer(#[dependency] InfiniteTileLayer),
+    ObjectLayer(#[dependency] ObjectLayer),
+    ImageLayer(#[dependency] ImageLayer),
+    GroupLayer(#[dependency] GroupLayer),
+}
+
+/// A layer containing other layers.
+///
+/// The offset and visibility of each child layer are relative to the group, the same as in Tiled.
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
+#[rkyv(serialize_bounds(__S: rkyv::ser::Writer + rkyv::ser::Allocator, __S::Error: rkyv::rancor::Source))]
+#[rkyv(deserialize_bounds(__D::Error: rkyv::rancor::Source))]
+#[rkyv(bytecheck(bounds(__C: rkyv::validation::ArchiveContext, __C::Error: rkyv::rancor::Source)))]
+#[rkyv(derive(Debug))]
+pub struct GroupLayer {
+    #[rkyv(omit_bounds)]
+    #[dependency]
+    pub layers: Vec<Layer>,
 }
 
-#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub struct ObjectLayer {
+    #[dependency]
     pub objects: Vec<ObjectData>,
 }
 
-impl AddDependencies for ArchivedObjectLayer {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        for object in self.objects.iter() {
-            object.add_dependencies(dependencies);
-        }
-    }
-}
-
-impl AddDependenciesMut for ObjectLayer {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        for object in self.objects.iter_mut() {
-            object.add_dependencies_mut(dependencies);
-        }
-    }
-}
-
-#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub struct ObjectData {
     pub id: u32,
+    #[dependency]
     pub shape: ObjectShape,
     pub name: String,
     pub x: f32,
     pub y: f32,
+    /// Rotation around `(x, y)` in degrees, clockwise, the same as in Tiled.
+    pub rotation: f32,
     pub visible: bool,
+    #[dependency]
     pub properties: Properties,
 }
 
-impl AddDependencies for ArchivedObjectData {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        self.properties.add_dependencies(dependencies);
-    }
-}
-
-impl AddDependenciesMut for ObjectData {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        self.properties.add_dependencies_mut(dependencies);
-    }
-}
-
-#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub enum ObjectShape {
-    Tile(Tile),
+    /// A tile drawn stretched to `width` x `height`, with `(x, y)` of the object at its
+    /// bottom-left corner.
+    ///
+    /// Tile objects always use the wide encoding, whatever the [`TileEncoding`] of the map.
+    Tile { tile: WideTile, width: f32, height: f32 },
     Rect { width: f32, height: f32 },
     Ellipse { width: f32, height: f32 },
     Polyline { points: Vec<(f32, f32)> },
     Polygon { points: Vec<(f32, f32)> },
     Point(f32, f32),
+    Text(#[dependency] TextData),
 }
 
-#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
+#[rkyv(derive(Debug))]
+pub struct TextData {
+    pub text: String,
+    /// The font family set in Tiled. Only informational, the font drawn with is [`TextData::font`].
+    pub font_family: String,
+    /// Path to the Playdate font (`.fnt`) to draw with, taken from the object's `font` file
+    /// property. If `None`, the system font is used.
+    #[dependency]
+    pub font: Option<String>,
+    pub pixel_size: u32,
+    pub wrap: bool,
+    pub halign: HorizontalAlignment,
+    pub valign: VerticalAlignment,
+    /// Width of the bounding box.
+    pub width: f32,
+    /// Height of the bounding box.
+    pub height: f32,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Archive, Deserialize, Serialize)]
+#[rkyv(derive(Debug, Copy, Clone, Eq, PartialEq))]
+pub enum HorizontalAlignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+    Justify,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Archive, Deserialize, Serialize)]
+#[rkyv(derive(Debug, Copy, Clone, Eq, PartialEq))]
+pub enum VerticalAlignment {
+    #[default]
+    Top,
+    Center,
+    Bottom,
+}
+
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub struct ImageLayer {
     /// The path for the image.
+    #[dependency]
     pub source: String,
     /// The width in pixels of the image.
     pub width: i32,
     /// The height in pixels of the image.
     pub height: i32,
+    /// Whether the image is repeated horizontally across the whole view.
+    pub repeat_x: bool,
+    /// Whether the image is repeated vertically across the whole view.
+    pub repeat_y: bool,
 }
 
-impl AddDependencies for ArchivedImageLayer {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        dependencies.insert(&self.source);
-    }
-}
-
-impl AddDependenciesMut for ImageLayer {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        dependencies.push(&mut self.source);
-    }
-}
-
-#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub struct FiniteTileLayer {
     pub width: u32,
     pub height: u32,
-    pub tiles: Vec<Option<Tile>>,
+    pub tiles: TileStorage,
     /// Optional, pre-baked image for layer.
     /// If `Some`, it will use the image as a single sprite on the Layer entity.
     /// If `None`, it will create a sprite on each tile entity.
+    #[dependency]
     pub image: Option<String>,
     pub layer_collision: Option<LayerCollision>,
 }
 
-impl AddDependencies for ArchivedFiniteTileLayer {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        if let Some(image) = self.image.as_ref() {
-            dependencies.insert(image);
-        }
-    }
-}
-
-impl AddDependenciesMut for FiniteTileLayer {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        if let Some(image) = self.image.as_mut() {
-            dependencies.push(image);
-        }
-    }
-}
-
-#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub struct InfiniteTileLayer {
+    #[dependency]
     pub chunks: HashMap<(i32, i32), ChunkData>,
 }
 
-impl AddDependenciesMut for InfiniteTileLayer {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        self.chunks.iter_mut()
-            .for_each(|(_, chunk)| chunk.add_dependencies_mut(dependencies));
-    }
-}
-
-impl AddDependencies for ArchivedInfiniteTileLayer {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        self.chunks.iter()
-            .for_each(|(_, chunk)| chunk.add_dependencies(dependencies));
-    }
-}
-
 impl ArchivedInfiniteTileLayer {
-
+    /// Obtains the chunk at the given chunk position, if there is one.
+    pub fn get_chunk(&self, x: i32, y: i32) -> Option<&ArchivedChunkData> {
+        self.chunks
+            .get(&ArchivedTuple2(ArchivedI32::from_native(x), ArchivedI32::from_native(y)))
+    }
 
     /// Obtains the tile data present at the position given.
     ///
     /// If the position given is invalid or the position is empty, this function will return [`None`].
     ///
     /// If you want to get a [`Tile`](`crate::Tile`) instead, use [`InfiniteTileLayer::get_tile()`].
-    pub fn get_tile_data(&self, x: i32, y: i32) -> Option<&Tile> {
+    pub fn get_tile_data(&self, x: i32, y: i32) -> Option<WideTile> {
         let chunk_pos = ArchivedChunkData::tile_to_chunk_pos(x, y);
-        let pos = ArchivedTuple2(ArchivedI32::from_native(chunk_pos.0), ArchivedI32::from_native(chunk_pos.1));
-        self.chunks
-            .get(&pos)
-            .and_then(|chunk| {
-                let relative_pos = (
-                    x - chunk_pos.0 * ChunkData::WIDTH as i32,
-                    y - chunk_pos.1 * ChunkData::HEIGHT as i32,
-                );
-                let chunk_index =
-                    (relative_pos.0 + relative_pos.1 * ChunkData::WIDTH as i32) as usize;
-                chunk.tiles.deref().get(chunk_index).map(ArchivedOption::as_ref)
-            })
-            .flatten()
+        self.get_chunk(chunk_pos.0, chunk_pos.1).and_then(|chunk| {
+            chunk.get_tile_data(
+                x - chunk_pos.0 * ChunkData::WIDTH as i32,
+                y - chunk_pos.1 * ChunkData::HEIGHT as i32,
+            )
+        })
     }
 
     /// Returns an iterator over only the data part of the chunks of this tile layer.
@@ -270,30 +283,16 @@ impl ArchivedInfiniteTileLayer {
     }
 }
 
-#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub struct ChunkData {
-    pub tiles: Box<[Option<Tile>; ChunkData::TILE_COUNT]>,
+    /// Always [`ChunkData::TILE_COUNT`] tiles long.
+    pub tiles: TileStorage,
     pub collision: Option<LayerCollision>,
+    #[dependency]
     pub image: Option<String>,
 }
 
-impl AddDependenciesMut for ChunkData {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        if let Some(image) = self.image.as_mut() {
-            dependencies.push(image);
-        }
-    }
-}
-
-impl AddDependencies for ArchivedChunkData {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        if let Some(image) = self.image.as_ref() {
-            dependencies.insert(image.as_str());
-        }
-    }
-}
-
 impl ChunkData {
     /// Infinite layer chunk width. This constant might change between versions, not counting as a
     /// breaking change.
@@ -312,19 +311,21 @@ impl ArchivedChunkData {
     /// If the position given is invalid or the position is empty, this function will return [`None`].
     ///
     /// If you want to get a [`LayerTile`](`crate::LayerTile`) instead, use [`Chunk::get_tile()`].
-    pub fn get_tile_data(&self, x: i32, y: i32) -> Option<&Tile> {
+    pub fn get_tile_data(&self, x: i32, y: i32) -> Option<WideTile> {
         if x < ChunkData::WIDTH as i32 && y < ChunkData::HEIGHT as i32 && x >= 0 && y >= 0 {
-            self.tiles[x as usize + y as usize * ChunkData::WIDTH as usize].as_ref()
+            self.tiles.get(x as usize + y as usize * ChunkData::WIDTH as usize)
         } else {
             None
         }
     }
 
     /// Returns the position of the chunk that contains the given tile position.
+    ///
+    /// Rounds towards negative infinity, so tile `-1` is in chunk `-1`, not chunk `0`.
     pub fn tile_to_chunk_pos(x: i32, y: i32) -> (i32, i32) {
         (
-            x / (ChunkData::WIDTH as i32),
-            y / (ChunkData::HEIGHT as i32),
+            x.div_euclid(ChunkData::WIDTH as i32),
+            y.div_euclid(ChunkData::HEIGHT as i32),
         )
     }
 }
@@ -448,6 +449,247 @@ impl Tile {
     }
 }
 
+/// How the tiles of the tile layers of a map are stored. Picked per map by the editor.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Archive, Deserialize, Serialize)]
+#[rkyv(derive(Debug, Copy, Clone, Eq, PartialEq))]
+pub enum TileEncoding {
+    /// [`Tile`], 2 bytes: up to 256 tiles per tileset and 16 tilesets.
+    #[default]
+    Compact,
+    /// [`WideTile`], 4 bytes: up to 65536 tiles per tileset and 256 tilesets.
+    Wide,
+}
+
+/// The tiles of a tile layer or chunk, in one of the [`TileEncoding`]s.
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[rkyv(derive(Debug))]
+pub enum TileStorage {
+    Compact(Vec<Option<Tile>>),
+    Wide(Vec<Option<WideTile>>),
+}
+
+impl TileStorage {
+    pub fn encoding(&self) -> TileEncoding {
+        match self {
+            Self::Compact(_) => TileEncoding::Compact,
+            Self::Wide(_) => TileEncoding::Wide,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Compact(tiles) => tiles.len(),
+            Self::Wide(tiles) => tiles.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<WideTile> {
+        match self {
+            Self::Compact(tiles) => tiles.get(index).copied().flatten().map(WideTile::from),
+            Self::Wide(tiles) => tiles.get(index).copied().flatten(),
+        }
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Option<WideTile>> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    /// Returns the same tiles in `encoding`, or the first tile that doesn't fit in it.
+    pub fn to_encoding(&self, encoding: TileEncoding) -> Result<Self, WideTile> {
+        Ok(match encoding {
+            TileEncoding::Compact => Self::Compact(
+                self.iter()
+                    .map(|tile| tile.map(|tile| tile.to_compact().ok_or(tile)).transpose())
+                    .collect::<Result<_, _>>()?,
+            ),
+            TileEncoding::Wide => Self::Wide(self.iter().collect()),
+        })
+    }
+}
+
+impl ArchivedTileStorage {
+    pub fn encoding(&self) -> TileEncoding {
+        match self {
+            Self::Compact(_) => TileEncoding::Compact,
+            Self::Wide(_) => TileEncoding::Wide,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Compact(tiles) => tiles.len(),
+            Self::Wide(tiles) => tiles.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the tile at `index` in the wide encoding, whichever encoding it is stored in.
+    pub fn get(&self, index: usize) -> Option<WideTile> {
+        match self {
+            Self::Compact(tiles) => tiles
+                .get(index)
+                .and_then(ArchivedOption::as_ref)
+                .map(|tile| WideTile::from(*tile)),
+            Self::Wide(tiles) => tiles.get(index).and_then(ArchivedOption::as_ref).copied(),
+        }
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Option<WideTile>> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+}
+
+/// A tile id or tileset index too large for [`WideTile`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct TileOverflow {
+    pub tile_id: u32,
+    pub tilemap_idx: u32,
+}
+
+impl Display for TileOverflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "tile {} of tileset {} doesn't fit in a tile (max tile id {}, max tileset index {})",
+            self.tile_id,
+            self.tilemap_idx,
+            u16::MAX,
+            u8::MAX,
+        )
+    }
+}
+
+/// A [`Tile`] with room for more tiles and tilesets, used by maps with
+/// [`TileEncoding::Wide`].
+#[derive(Copy, Clone, Eq, PartialEq, Archive, Deserialize, Serialize, Portable, CheckBytes)]
+#[repr(C)]
+#[rkyv(as = WideTile)]
+pub struct WideTile {
+    /// Id of tile in tilemap, little endian.
+    tile_id: [u8; 2],
+    /// Index of tilemap in map.
+    tilemap_idx: u8,
+    /// `0000 XYD1`, the same as the low bits of the mask of [`Tile`].
+    mask: NonZeroU8,
+}
+
+impl WideTile {
+    pub fn new(
+        tile_id: u32,
+        flip_x: bool,
+        flip_y: bool,
+        flip_d: bool,
+        tilemap_idx: u32,
+    ) -> Result<Self, TileOverflow> {
+        let overflow = TileOverflow {
+            tile_id,
+            tilemap_idx,
+        };
+        let id = u16::try_from(tile_id).map_err(|_| overflow)?;
+        let idx = u8::try_from(tilemap_idx).map_err(|_| overflow)?;
+
+        let mut mask = 1;
+        if flip_x {
+            mask |= 1 << 3;
+        }
+        if flip_y {
+            mask |= 1 << 2;
+        }
+        if flip_d {
+            mask |= 1 << 1;
+        }
+
+        Ok(Self {
+            tile_id: id.to_le_bytes(),
+            tilemap_idx: idx,
+            mask: NonZeroU8::new(mask).unwrap(),
+        })
+    }
+
+    pub fn tile_id(&self) -> u16 {
+        u16::from_le_bytes(self.tile_id)
+    }
+
+    pub fn get_flip_x(&self) -> bool {
+        (self.mask.get() >> 3) & 1 == 1
+    }
+
+    pub fn get_flip_y(&self) -> bool {
+        (self.mask.get() >> 2) & 1 == 1
+    }
+
+    pub fn get_flip_d(&self) -> bool {
+        (self.mask.get() >> 1) & 1 == 1
+    }
+
+    pub fn get_tilemap_idx(&self) -> u8 {
+        self.tilemap_idx
+    }
+
+    /// Returns the same tile in the compact encoding, if it fits.
+    pub fn to_compact(self) -> Option<Tile> {
+        let id = u8::try_from(self.tile_id()).ok()?;
+        if self.tilemap_idx >= 16 {
+            return None;
+        }
+
+        Some(Tile::new(
+            id,
+            self.get_flip_x(),
+            self.get_flip_y(),
+            self.get_flip_d(),
+            self.tilemap_idx,
+        ))
+    }
+}
+
+impl From<Tile> for WideTile {
+    fn from(tile: Tile) -> Self {
+        Self {
+            tile_id: (tile.tile_id as u16).to_le_bytes(),
+            tilemap_idx: tile.get_tilemap_idx(),
+            // the flip bits and the niche bit are in the same place
+            mask: NonZeroU8::new(tile.mask.get() & 0b0000_1111).unwrap(),
+        }
+    }
+}
+
+impl Display for WideTile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Tile(id={}, map={}, flips=[",
+            self.tile_id(),
+            self.get_tilemap_idx()
+        )?;
+
+        if self.get_flip_x() {
+            write!(f, "X")?;
+        }
+        if self.get_flip_y() {
+            write!(f, "Y")?;
+        }
+        if self.get_flip_d() {
+            write!(f, "D")?;
+        }
+
+        write!(f, "])")
+    }
+}
+
+impl core::fmt::Debug for WideTile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
 impl core::fmt::Display for Tile {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
@@ -479,7 +721,9 @@ impl core::fmt::Debug for Tile {
 
 #[cfg(test)]
 mod test {
-    use crate::tilemap::Tile;
+    use crate::tilemap::{ArchivedChunkData, Tile, TileEncoding, TileStorage, WideTile};
+    use alloc::vec;
+    use alloc::vec::Vec;
 
     #[test]
     pub fn option_tile_same_size() {
@@ -490,4 +734,48 @@ mod test {
     pub fn tile_is_16_bits() {
         assert_eq!(size_of::<Tile>(), 2);
     }
+
+    #[test]
+    pub fn option_wide_tile_same_size() {
+        assert_eq!(size_of::<WideTile>(), size_of::<Option<WideTile>>());
+        assert_eq!(size_of::<WideTile>(), 4);
+    }
+
+    #[test]
+    pub fn wide_tile_roundtrip() {
+        let tile = Tile::new(200, true, false, true, 15);
+        let wide = WideTile::from(tile);
+        assert_eq!(wide.tile_id(), 200);
+        assert_eq!(wide.get_tilemap_idx(), 15);
+        assert!(wide.get_flip_x() && !wide.get_flip_y() && wide.get_flip_d());
+        assert_eq!(wide.to_compact(), Some(tile));
+
+        let wide = WideTile::new(300, false, true, false, 2).unwrap();
+        assert_eq!(wide.tile_id(), 300);
+        assert_eq!(wide.to_compact(), None);
+        assert!(WideTile::new(70_000, false, false, false, 0).is_err());
+        assert!(WideTile::new(0, false, false, false, 256).is_err());
+    }
+
+    #[test]
+    pub fn tile_storage_encoding() {
+        let small = WideTile::new(255, false, false, false, 1).unwrap();
+        let large = WideTile::new(256, false, false, false, 1).unwrap();
+
+        let storage = TileStorage::Wide(vec![Some(small), None]);
+        let compact = storage.to_encoding(TileEncoding::Compact).unwrap();
+        assert_eq!(compact.encoding(), TileEncoding::Compact);
+        assert_eq!(compact.iter().collect::<Vec<_>>(), vec![Some(small), None]);
+
+        let storage = TileStorage::Wide(vec![Some(small), Some(large)]);
+        assert_eq!(storage.to_encoding(TileEncoding::Compact), Err(large));
+    }
+
+    #[test]
+    pub fn tile_to_chunk_pos_negative() {
+        assert_eq!(ArchivedChunkData::tile_to_chunk_pos(0, 15), (0, 0));
+        assert_eq!(ArchivedChunkData::tile_to_chunk_pos(16, 31), (1, 1));
+        assert_eq!(ArchivedChunkData::tile_to_chunk_pos(-1, -16), (-1, -1));
+        assert_eq!(ArchivedChunkData::tile_to_chunk_pos(-17, -15), (-2, -1));
+    }
 }
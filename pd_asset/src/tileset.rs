@@ -1,49 +1,38 @@
-use crate::dependencies::{AddDependencies, AddDependenciesMut};
+use crate::dependencies::AddDependencies;
+use crate::header::{ArchiveKind, AssetKind};
 use crate::properties::Properties;
 use alloc::string::String;
 use alloc::vec::Vec;
-use hashbrown::HashSet;
 use rkyv::{Archive, Deserialize, Serialize};
 
-#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub struct Tileset {
+    #[dependency]
     pub image_path: String,
+    #[dependency]
     pub tiles: Vec<TileData>,
 }
 
-impl AddDependencies for ArchivedTileset {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        dependencies.insert(&self.image_path);
-        for tile in self.tiles.iter() {
-            tile.add_dependencies(dependencies);
-        }
-    }
+impl ArchiveKind for ArchivedTileset {
+    const KIND: AssetKind = AssetKind::Tileset;
 }
 
-impl AddDependenciesMut for Tileset {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        dependencies.push(&mut self.image_path);
-        for tile in self.tiles.iter_mut() {
-            tile.add_dependencies_mut(dependencies);
-        }
-    }
-}
-
-#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Archive, Deserialize, Serialize, AddDependencies)]
 #[rkyv(derive(Debug))]
 pub struct TileData {
+    #[dependency]
     pub properties: Properties,
+    /// The frames of the tile's animation, if it has one.
+    pub animation: Option<Vec<Frame>>,
 }
 
-impl AddDependencies for ArchivedTileData {
-    fn add_dependencies<'a: 'b, 'b>(&'a self, dependencies: &mut HashSet<&'b str>) {
-        self.properties.add_dependencies(dependencies);
-    }
-}
-
-impl AddDependenciesMut for TileData {
-    fn add_dependencies_mut<'a: 'b, 'b>(&'a mut self, dependencies: &mut Vec<&'b mut String>) {
-        self.properties.add_dependencies_mut(dependencies);
-    }
+/// A single frame of a tile animation.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Archive, Deserialize, Serialize)]
+#[rkyv(derive(Debug, Copy, Clone, Eq, PartialEq))]
+pub struct Frame {
+    /// The id of the tile to show, in the same tileset.
+    pub tile_id: u32,
+    /// How long the frame is shown for, in milliseconds.
+    pub duration: u32,
 }
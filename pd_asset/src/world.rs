@@ -0,0 +1,129 @@
+use crate::dependencies::AddDependencies;
+use crate::header::{ArchiveKind, AssetKind};
+use alloc::string::String;
+use alloc::vec::Vec;
+use rkyv::{Archive, Deserialize, Serialize};
+
+/// A Tiled world: several maps placed at pixel offsets from each other.
+#[derive(Archive, Serialize, Deserialize, Debug, AddDependencies)]
+#[rkyv(derive(Debug))]
+pub struct World {
+    #[dependency]
+    pub maps: Vec<WorldMap>,
+}
+
+/// A map in a [`World`] and the rect it covers, in pixels.
+#[derive(Archive, Serialize, Deserialize, Debug, AddDependencies)]
+#[rkyv(derive(Debug))]
+pub struct WorldMap {
+    #[dependency]
+    pub path: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ArchivedWorldMap {
+    /// Returns true if the point is inside the map.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let [min_x, min_y, max_x, max_y] = self.rect();
+        min_x <= x && x < max_x && min_y <= y && y < max_y
+    }
+
+    /// Returns true if the maps overlap, or are at most `margin` pixels apart.
+    pub fn is_near(&self, other: &ArchivedWorldMap, margin: i32) -> bool {
+        let [a_min_x, a_min_y, a_max_x, a_max_y] = self.rect();
+        let [b_min_x, b_min_y, b_max_x, b_max_y] = other.rect();
+        a_min_x - margin <= b_max_x
+            && b_min_x <= a_max_x + margin
+            && a_min_y - margin <= b_max_y
+            && b_min_y <= a_max_y + margin
+    }
+
+    /// `[min_x, min_y, max_x, max_y]`, with the max exclusive.
+    pub fn rect(&self) -> [i32; 4] {
+        let x = self.x.to_native();
+        let y = self.y.to_native();
+        [
+            x,
+            y,
+            x + self.width.to_native() as i32,
+            y + self.height.to_native() as i32,
+        ]
+    }
+}
+
+impl ArchiveKind for ArchivedWorld {
+    const KIND: AssetKind = AssetKind::World;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::world::{ArchivedWorld, World, WorldMap};
+    use alloc::string::String;
+    use rkyv::access;
+    use rkyv::rancor::Error;
+    use rkyv::util::AlignedVec;
+
+    /// Archives a world with a map at each `[x, y, width, height]`.
+    fn archive(maps: &[(i32, i32, u32, u32)]) -> AlignedVec {
+        let maps = maps
+            .iter()
+            .map(|&(x, y, width, height)| WorldMap {
+                path: String::new(),
+                x,
+                y,
+                width,
+                height,
+            })
+            .collect();
+        rkyv::to_bytes::<Error>(&World { maps }).unwrap()
+    }
+
+    #[test]
+    pub fn world_map_rect_negative() {
+        let bytes = archive(&[(-32, -16, 32, 16)]);
+        let world = access::<ArchivedWorld, Error>(&bytes).unwrap();
+        assert_eq!(world.maps[0].rect(), [-32, -16, 0, 0]);
+    }
+
+    #[test]
+    pub fn world_map_contains_edges() {
+        let bytes = archive(&[(-32, -16, 32, 16)]);
+        let map = &access::<ArchivedWorld, Error>(&bytes).unwrap().maps[0];
+        assert!(map.contains(-32, -16));
+        assert!(map.contains(-1, -1));
+        // the max is exclusive
+        assert!(!map.contains(0, -1));
+        assert!(!map.contains(-1, 0));
+        assert!(!map.contains(-33, -16));
+        assert!(!map.contains(-32, -17));
+    }
+
+    #[test]
+    pub fn world_map_is_near_margin() {
+        let bytes = archive(&[
+            (0, 0, 100, 100),
+            (100, 0, 100, 100),
+            (110, 0, 100, 100),
+            (-150, -150, 50, 50),
+            (50, 50, 10, 10),
+        ]);
+        let maps = &access::<ArchivedWorld, Error>(&bytes).unwrap().maps;
+        let [map, touching, apart, diagonal, inside] = [0, 1, 2, 3, 4].map(|i| &maps[i]);
+
+        assert!(map.is_near(inside, 0));
+        assert!(map.is_near(touching, 0));
+        assert!(touching.is_near(map, 0));
+
+        assert!(!map.is_near(apart, 0));
+        assert!(!map.is_near(apart, 9));
+        assert!(map.is_near(apart, 10));
+        assert!(apart.is_near(map, 10));
+
+        assert!(!map.is_near(diagonal, 99));
+        assert!(map.is_near(diagonal, 100));
+        assert!(diagonal.is_near(map, 100));
+    }
+}